@@ -0,0 +1,48 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use rusty_parens::tokenizer::{SliceTokenizer, Tokenizer};
+
+/// About 4 MB of s-expressions resembling structured log records.
+fn sample_input() -> String {
+    let mut input = String::new();
+    let mut id = 0;
+    while input.len() < 4 * 1024 * 1024 {
+        input.push_str(&format!(
+            "(event {{:id {} :level \"info\" :latency 12.5 :ratio 3/4}} \
+             [\"user-{}\" \"path/with \\\"escape\\\"\"] #| request |#) ; logged\n",
+            id, id
+        ));
+        id += 1;
+    }
+    input
+}
+
+fn tokenizers(c: &mut Criterion) {
+    let input = sample_input();
+    let mut group = c.benchmark_group("tokenize");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.sample_size(10);
+
+    group.bench_function("Tokenizer", |b| {
+        b.iter(|| {
+            let mut tokenizer = Tokenizer::from_string(black_box(&input));
+            let mut count = 0;
+            while tokenizer.next().is_ok() {
+                count += 1;
+            }
+            count
+        })
+    });
+
+    group.bench_function("SliceTokenizer", |b| {
+        b.iter(|| {
+            SliceTokenizer::new(black_box(&input))
+                .map(Result::unwrap)
+                .count()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, tokenizers);
+criterion_main!(benches);
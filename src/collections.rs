@@ -0,0 +1,416 @@
+use crate::reader::Expression;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+
+/// Map of expressions that keeps its entries in insertion order.
+///
+/// Keys are compared structurally, and two maps are equal when they hold the same
+/// entries regardless of their order.
+#[derive(Clone, Default)]
+pub struct Map {
+    table: Table<Expression>,
+}
+
+impl Map {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts an entry, returning the previous value stored under `key`.
+    pub fn insert(&mut self, key: Expression, value: Expression) -> Option<Expression> {
+        self.table.insert(key, value)
+    }
+
+    pub fn get(&self, key: &Expression) -> Option<&Expression> {
+        self.table.get(key)
+    }
+
+    pub fn contains_key(&self, key: &Expression) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.table.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Expression, &Expression)> {
+        self.table.iter()
+    }
+}
+
+impl Debug for Map {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl PartialEq for Map {
+    fn eq(&self, other: &Map) -> bool {
+        self.len() == other.len() && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl Hash for Map {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        unordered_hash(self.iter()).hash(state)
+    }
+}
+
+impl FromIterator<(Expression, Expression)> for Map {
+    fn from_iter<T: IntoIterator<Item = (Expression, Expression)>>(iter: T) -> Self {
+        let mut map = Map::new();
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+/// Set of expressions that keeps its elements in insertion order.
+///
+/// Like `Map` it compares elements structurally, and two sets are equal when they
+/// hold the same elements regardless of their order.
+#[derive(Clone, Default)]
+pub struct Set {
+    table: Table<()>,
+}
+
+impl Set {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an element, returning whether it was not in the set yet.
+    pub fn insert(&mut self, element: Expression) -> bool {
+        self.table.insert(element, ()).is_none()
+    }
+
+    /// Removes an element, returning whether it was in the set.
+    pub fn remove(&mut self, element: &Expression) -> bool {
+        self.table.remove(element).is_some()
+    }
+
+    pub fn contains(&self, element: &Expression) -> bool {
+        self.table.get(element).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.table.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Expression> {
+        self.table.iter().map(|(element, _)| element)
+    }
+
+    /// Elements of `self` followed by the elements of `other` missing from `self`.
+    pub fn union(&self, other: &Set) -> Set {
+        self.iter().chain(other.iter()).cloned().collect()
+    }
+
+    pub fn intersection(&self, other: &Set) -> Set {
+        self.iter().filter(|e| other.contains(e)).cloned().collect()
+    }
+
+    pub fn difference(&self, other: &Set) -> Set {
+        self.iter()
+            .filter(|e| !other.contains(e))
+            .cloned()
+            .collect()
+    }
+}
+
+impl Debug for Set {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl PartialEq for Set {
+    fn eq(&self, other: &Set) -> bool {
+        self.len() == other.len() && self.iter().all(|e| other.contains(e))
+    }
+}
+
+impl Hash for Set {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        unordered_hash(self.iter()).hash(state)
+    }
+}
+
+impl FromIterator<Expression> for Set {
+    fn from_iter<T: IntoIterator<Item = Expression>>(iter: T) -> Self {
+        let mut set = Set::new();
+        for element in iter {
+            set.insert(element);
+        }
+        set
+    }
+}
+
+/// Entries in insertion order, indexed by the hash of their keys.
+///
+/// Removed entries leave an empty slot behind so the positions in the index stay
+/// valid, the slots are compacted once most of them are empty.
+#[derive(Clone)]
+struct Table<V> {
+    slots: Vec<Option<(Expression, V)>>,
+    /// Positions in `slots` of the keys with the given hash.
+    index: HashMap<u64, Vec<usize>>,
+    len: usize,
+}
+
+impl<V> Default for Table<V> {
+    fn default() -> Self {
+        Table {
+            slots: Vec::new(),
+            index: HashMap::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<V> Table<V> {
+    fn position(&self, hash: u64, key: &Expression) -> Option<usize> {
+        let positions = self.index.get(&hash)?;
+        positions
+            .iter()
+            .copied()
+            .find(|&position| matches!(&self.slots[position], Some((k, _)) if k == key))
+    }
+
+    fn get(&self, key: &Expression) -> Option<&V> {
+        let position = self.position(hash_of(key), key)?;
+        self.slots[position].as_ref().map(|(_, value)| value)
+    }
+
+    fn insert(&mut self, key: Expression, value: V) -> Option<V> {
+        let hash = hash_of(&key);
+        match self.position(hash, &key) {
+            Some(position) => {
+                let (_, old) = self.slots[position].as_mut()?;
+                Some(std::mem::replace(old, value))
+            }
+            None => {
+                self.index.entry(hash).or_default().push(self.slots.len());
+                self.slots.push(Some((key, value)));
+                self.len += 1;
+                None
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &Expression) -> Option<V> {
+        let hash = hash_of(key);
+        let position = self.position(hash, key)?;
+        let (_, value) = self.slots[position].take()?;
+        self.len -= 1;
+        let positions = self.index.get_mut(&hash)?;
+        positions.retain(|&p| p != position);
+        if positions.is_empty() {
+            self.index.remove(&hash);
+        }
+        if self.slots.len() > 2 * self.len + 8 {
+            self.compact();
+        }
+        Some(value)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&Expression, &V)> {
+        self.slots.iter().flatten().map(|(k, v)| (k, v))
+    }
+
+    fn compact(&mut self) {
+        self.slots.retain(Option::is_some);
+        self.index.clear();
+        for (position, (key, _)) in self.slots.iter().flatten().enumerate() {
+            self.index.entry(hash_of(key)).or_default().push(position);
+        }
+    }
+}
+
+fn hash_of(value: &impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Hash that does not depend on the order of `items`, for collections whose
+/// equality ignores order.
+fn unordered_hash<T: Hash>(items: impl Iterator<Item = T>) -> u64 {
+    items.map(|item| hash_of(&item)).fold(0, u64::wrapping_add)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reader::Expression::{Float, Integer, String};
+
+    #[test]
+    fn should_replace_value_of_existing_key() {
+        // given
+        let mut map = Map::new();
+        map.insert(Integer(1), String("one".to_owned()));
+
+        // when
+        let old = map.insert(Integer(1), String("uno".to_owned()));
+
+        // then
+        assert_eq!(Some(String("one".to_owned())), old);
+        assert_eq!(Some(&String("uno".to_owned())), map.get(&Integer(1)));
+        assert_eq!(1, map.len());
+    }
+
+    #[test]
+    fn should_keep_entries_in_insertion_order() {
+        // given
+        let map: Map = (0..100)
+            .rev()
+            .map(|i| (Integer(i), Integer(i * 2)))
+            .collect();
+
+        // expect
+        assert_eq!(100, map.len());
+        assert_eq!(Some(&Integer(84)), map.get(&Integer(42)));
+        assert_eq!(None, map.get(&Integer(100)));
+        let keys: Vec<_> = map.iter().map(|(k, _)| k.clone()).collect();
+        assert_eq!((0..100).rev().map(Integer).collect::<Vec<_>>(), keys);
+    }
+
+    #[test]
+    fn should_compare_maps_regardless_of_entry_order() {
+        // given
+        let first: Map = vec![(Integer(1), Integer(2)), (Integer(3), Integer(4))]
+            .into_iter()
+            .collect();
+        let second: Map = vec![(Integer(3), Integer(4)), (Integer(1), Integer(2))]
+            .into_iter()
+            .collect();
+        let third: Map = vec![(Integer(3), Integer(4)), (Integer(1), Integer(5))]
+            .into_iter()
+            .collect();
+
+        // expect
+        assert_eq!(first, second);
+        assert_ne!(first, third);
+    }
+
+    #[test]
+    fn should_hash_maps_regardless_of_entry_order() {
+        // given
+        let first: Map = vec![(Integer(1), Integer(2)), (Integer(3), Integer(4))]
+            .into_iter()
+            .collect();
+        let second: Map = vec![(Integer(3), Integer(4)), (Integer(1), Integer(2))]
+            .into_iter()
+            .collect();
+
+        // expect
+        assert_eq!(hash_of(&first), hash_of(&second));
+    }
+
+    #[test]
+    fn should_keep_set_elements_unique() {
+        // given
+        let mut set: Set = vec![Integer(1), Integer(2), Integer(1)]
+            .into_iter()
+            .collect();
+
+        // when
+        let added = set.insert(Integer(2));
+        let removed = set.remove(&Integer(1));
+
+        // then
+        assert!(!added);
+        assert!(removed);
+        assert_eq!(vec![&Integer(2)], set.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn should_combine_sets() {
+        // given
+        let first: Set = vec![Integer(1), Integer(2), Integer(3)]
+            .into_iter()
+            .collect();
+        let second: Set = vec![Integer(4), Integer(3), Integer(2)]
+            .into_iter()
+            .collect();
+        let set = |items: Vec<i64>| items.into_iter().map(Integer).collect::<Set>();
+
+        // expect
+        assert_eq!(set(vec![1, 2, 3, 4]), first.union(&second));
+        assert_eq!(set(vec![2, 3]), first.intersection(&second));
+        assert_eq!(set(vec![1]), first.difference(&second));
+    }
+
+    #[test]
+    fn should_compare_and_hash_sets_regardless_of_order() {
+        // given
+        let first: Set = vec![Integer(1), String("a".to_owned())]
+            .into_iter()
+            .collect();
+        let second: Set = vec![String("a".to_owned()), Integer(1)]
+            .into_iter()
+            .collect();
+
+        // expect
+        assert_eq!(first, second);
+        assert_eq!(hash_of(&first), hash_of(&second));
+        assert_ne!(first, vec![Integer(1)].into_iter().collect());
+    }
+
+    #[test]
+    fn should_keep_nan_elements_and_keys_unique() {
+        // given
+        let mut set: Set = vec![Float(f64::NAN), Integer(1), Float(-f64::NAN)]
+            .into_iter()
+            .collect();
+        let mut map = Map::new();
+        map.insert(Float(f64::NAN), Integer(1));
+
+        // when
+        let added = set.insert(Float(f64::NAN));
+        let old = map.insert(Float(f64::NAN), Integer(2));
+
+        // then
+        assert!(!added);
+        assert_eq!(2, set.len());
+        assert!(set.contains(&Float(f64::NAN)));
+        assert_eq!(Some(Integer(1)), old);
+        assert_eq!(1, map.len());
+    }
+
+    #[test]
+    fn should_find_elements_after_removing_others() {
+        // given
+        let mut set: Set = (0..100).map(Integer).collect();
+
+        // when
+        for i in (0..100).filter(|i| i % 3 != 0) {
+            assert!(set.remove(&Integer(i)));
+        }
+
+        // then
+        assert_eq!(34, set.len());
+        assert!(set.contains(&Integer(99)));
+        assert!(!set.contains(&Integer(98)));
+        assert!(!set.remove(&Integer(98)));
+        assert!(set.insert(Integer(98)));
+        let expected: Vec<_> = (0..100)
+            .filter(|i| i % 3 == 0)
+            .chain(Some(98))
+            .map(Integer)
+            .collect();
+        assert_eq!(expected, set.iter().cloned().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,643 @@
+use crate::reader::Expression::{self, Identifier, List, Vector};
+use crate::reader::ReaderError::{
+    MismatchedDelimiter, MissingForm, UnexpectedClosingDelimiter, UnexpectedEndOfInput,
+};
+use crate::reader::{
+    attach_meta, collect_map, collect_set, is_tag, meta_map, read_atom, TagRegistry,
+};
+use crate::span::{Span, SpanTree};
+use crate::tokenizer::{SliceToken, SliceTokenizer, Unclosed};
+use failure::Error;
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+
+/// Kind of a node of the concrete syntax tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    /// Whole input.
+    Root,
+    List,
+    Vector,
+    Map,
+    Set,
+    /// `'`, `` ` ``, `~` or `~@` and the form it applies to.
+    Quoted,
+    /// `^`, the metadata and the form it is attached to.
+    Meta,
+    /// `#tag` and the form it applies to.
+    Tagged,
+    /// `#_` and the form it comments out.
+    Discarded,
+    /// Identifier, keyword or literal value.
+    Atom,
+    /// Delimiter of a collection, reader macro or tag of a tagged literal.
+    Punctuation,
+    /// Whitespace and commas.
+    Whitespace,
+    /// Line or block comment.
+    Comment,
+}
+
+/// Node of the concrete syntax tree covering bytes `range` of the source.
+///
+/// Leaves are tokens and trivia, inner nodes are made of their children with
+/// nothing in between, so the leaves put together give back the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub kind: NodeKind,
+    pub range: Range<usize>,
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    fn leaf(kind: NodeKind, range: Range<usize>) -> Self {
+        Self {
+            kind,
+            range,
+            children: vec![],
+        }
+    }
+
+    fn inner(kind: NodeKind, children: Vec<Node>) -> Self {
+        let start = children.first().map_or(0, |child| child.range.start);
+        let end = children.last().map_or(start, |child| child.range.end);
+        Self {
+            kind,
+            range: start..end,
+            children,
+        }
+    }
+
+    pub fn is_trivia(&self) -> bool {
+        matches!(self.kind, NodeKind::Whitespace | NodeKind::Comment)
+    }
+
+    /// Whether the reader reads an expression from this node. Discarded forms
+    /// and trivia are not forms.
+    pub fn is_form(&self) -> bool {
+        !matches!(
+            self.kind,
+            NodeKind::Root
+                | NodeKind::Discarded
+                | NodeKind::Punctuation
+                | NodeKind::Whitespace
+                | NodeKind::Comment
+        )
+    }
+
+    /// Children of the node that are forms.
+    pub fn forms(&self) -> impl Iterator<Item = &Node> {
+        self.children.iter().filter(|child| child.is_form())
+    }
+}
+
+/// Source parsed into a lossless concrete syntax tree.
+///
+/// Unlike the `Reader`, the tree keeps whitespace, comments and discarded forms,
+/// so it suits tools that rewrite source code. Displaying it gives back the
+/// source byte for byte.
+#[derive(Debug, Clone)]
+pub struct Cst<'a> {
+    source: &'a str,
+    root: Node,
+    lines: LineStarts,
+}
+
+impl<'a> Cst<'a> {
+    /// Parses `source`, failing on the same syntax errors the `Reader` does.
+    pub fn parse(source: &'a str) -> Result<Self, Error> {
+        let mut parser = Parser {
+            source,
+            tokens: SliceTokenizer::new(source),
+            position: 0,
+            lines: LineStarts::new(source),
+        };
+        let mut root = parser.parse_root()?;
+        root.range = 0..source.len();
+        Ok(Self {
+            source,
+            root,
+            lines: parser.lines,
+        })
+    }
+
+    pub fn root(&self) -> &Node {
+        &self.root
+    }
+
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    pub fn text(&self, node: &Node) -> &'a str {
+        &self.source[node.range.clone()]
+    }
+
+    /// Expressions of the top-level forms, the same a `Reader` reads from the
+    /// source.
+    pub fn expressions(&self) -> Result<Vec<Expression>, Error> {
+        let forms = self.read_with(&TagRegistry::new())?;
+        Ok(forms.into_iter().map(|(expr, _)| expr).collect())
+    }
+
+    /// Expressions of the top-level forms along with their spans, tagged
+    /// literals being read by the handlers in `tags`.
+    pub fn read_with(&self, tags: &TagRegistry) -> Result<Vec<(Expression, SpanTree)>, Error> {
+        self.read_forms(tags, &self.root)
+            .map(|(contents, trees)| contents.into_iter().zip(trees).collect())
+    }
+
+    /// Reads the forms among children of `node`. Discarded forms are read too, like
+    /// the `Reader` does, so that they fail the same way.
+    fn read_forms(
+        &self,
+        tags: &TagRegistry,
+        node: &Node,
+    ) -> Result<(Vec<Expression>, Vec<SpanTree>), Error> {
+        let mut contents = vec![];
+        let mut trees = vec![];
+        for form in &node.children {
+            if form.kind == NodeKind::Discarded {
+                self.read_forms(tags, form)?;
+            }
+            if !form.is_form() {
+                continue;
+            }
+            let (expr, tree) = self.read_node(tags, form)?;
+            contents.push(expr);
+            trees.push(tree);
+        }
+        Ok((contents, trees))
+    }
+
+    fn read_node(&self, tags: &TagRegistry, node: &Node) -> Result<(Expression, SpanTree), Error> {
+        let span = self.lines.span(self.source, &node.range);
+        let (mut contents, mut children) = self.read_forms(tags, node)?;
+        let prefix = || self.lines.span(self.source, &node.children[0].range);
+        Ok(match node.kind {
+            NodeKind::Atom => {
+                let (token, _) = SliceTokenizer::new(self.text(node))
+                    .next_token()?
+                    .expect("atom is a single token");
+                (read_atom(token.into_owned(), &span)?, SpanTree::leaf(span))
+            }
+            NodeKind::List => (List(contents), SpanTree { span, children }),
+            NodeKind::Vector => (Vector(contents), SpanTree { span, children }),
+            NodeKind::Map => collect_map(contents, SpanTree { span, children })?,
+            NodeKind::Set => collect_set(contents, SpanTree { span, children })?,
+            NodeKind::Quoted => {
+                let symbol = match self.text(&node.children[0]) {
+                    "'" => "quote",
+                    "`" => "quasiquote",
+                    "~" => "unquote",
+                    _ => "unquote-splicing",
+                };
+                children.insert(0, SpanTree::leaf(prefix()));
+                contents.insert(0, Identifier(symbol.to_owned()));
+                (List(contents), SpanTree { span, children })
+            }
+            NodeKind::Meta => {
+                let form_span = children.pop().expect("metadata target is read");
+                let form = contents.pop().expect("metadata target is read");
+                let meta_span = children.pop().expect("metadata is read");
+                let meta = contents.pop().expect("metadata is read");
+                let meta = meta_map(meta, meta_span.span)?;
+                attach_meta(meta, form, form_span, &prefix())?
+            }
+            NodeKind::Tagged => {
+                let form = contents.pop().expect("tagged form is read");
+                let tag = &self.text(&node.children[0])[1..];
+                tags.tagged_value(tag, form, span)?
+            }
+            kind => unreachable!("{:?} is not a form", kind),
+        })
+    }
+}
+
+/// Prints the leaves of the tree, which gives back the parsed source.
+impl Display for Cst<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        fn write_node(f: &mut Formatter, source: &str, node: &Node) -> std::fmt::Result {
+            if node.children.is_empty() {
+                return f.write_str(&source[node.range.clone()]);
+            }
+            node.children
+                .iter()
+                .try_for_each(|child| write_node(f, source, child))
+        }
+        write_node(f, self.source, &self.root)
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: SliceTokenizer<'a>,
+    /// End of the last token read.
+    position: usize,
+    lines: LineStarts,
+}
+
+impl<'a> Parser<'a> {
+    fn parse_root(&mut self) -> Result<Node, Error> {
+        let mut children = vec![];
+        while let Some((token, range)) = self.next_token(&mut children)? {
+            if let Some(closing) = closing_char(&token) {
+                return Err(UnexpectedClosingDelimiter(closing, self.span(&range)).into());
+            }
+            let form = self.parse_form(token, range)?;
+            children.push(form);
+        }
+        Ok(Node::inner(NodeKind::Root, children))
+    }
+
+    /// Next token, the trivia in front of it being added to `children`.
+    fn next_token(
+        &mut self,
+        children: &mut Vec<Node>,
+    ) -> Result<Option<(SliceToken<'a>, Range<usize>)>, Error> {
+        let next = self.tokens.next_token()?;
+        let end = next
+            .as_ref()
+            .map_or(self.source.len(), |(_, range)| range.start);
+        push_trivia(self.source, self.position..end, children);
+        self.position = end;
+        if let Some((_, range)) = &next {
+            self.position = range.end;
+        }
+        Ok(next)
+    }
+
+    /// Next token of a form opened at `range`, which is incomplete if there is
+    /// none.
+    fn next_token_inside(
+        &mut self,
+        children: &mut Vec<Node>,
+        unclosed: Unclosed,
+        range: &Range<usize>,
+    ) -> Result<(SliceToken<'a>, Range<usize>), Error> {
+        match self.next_token(children)? {
+            Some(next) => Ok(next),
+            None => Err(UnexpectedEndOfInput(unclosed, self.span(range)).into()),
+        }
+    }
+
+    fn parse_form(&mut self, token: SliceToken<'a>, range: Range<usize>) -> Result<Node, Error> {
+        match token {
+            SliceToken::LeftParen => self.parse_collection(NodeKind::List, '(', range),
+            SliceToken::LeftBracket => self.parse_collection(NodeKind::Vector, '[', range),
+            SliceToken::LeftBrace => self.parse_collection(NodeKind::Map, '{', range),
+            SliceToken::LeftSetBrace => self.parse_collection(NodeKind::Set, '#', range),
+            SliceToken::Quote => self.parse_prefixed(NodeKind::Quoted, "'", range, 1),
+            SliceToken::Quasiquote => self.parse_prefixed(NodeKind::Quoted, "`", range, 1),
+            SliceToken::Unquote => self.parse_prefixed(NodeKind::Quoted, "~", range, 1),
+            SliceToken::UnquoteSplicing => self.parse_prefixed(NodeKind::Quoted, "~@", range, 1),
+            SliceToken::Meta => self.parse_prefixed(NodeKind::Meta, "^", range, 2),
+            SliceToken::DatumComment => self.parse_prefixed(NodeKind::Discarded, "#_", range, 1),
+            SliceToken::Identifier(ident) if is_tag(ident) => {
+                self.parse_prefixed(NodeKind::Tagged, "tagged literal", range, 1)
+            }
+            _ => Ok(Node::leaf(NodeKind::Atom, range)),
+        }
+    }
+
+    /// Parses forms up to the delimiter closing the `opening` one found at `range`.
+    fn parse_collection(
+        &mut self,
+        kind: NodeKind,
+        opening: char,
+        range: Range<usize>,
+    ) -> Result<Node, Error> {
+        let expected = match opening {
+            '[' => ']',
+            '{' | '#' => '}',
+            _ => ')',
+        };
+        let mut children = vec![Node::leaf(NodeKind::Punctuation, range.clone())];
+        loop {
+            let (token, token_range) =
+                self.next_token_inside(&mut children, Unclosed::Collection(opening), &range)?;
+            if let Some(closing) = closing_char(&token) {
+                if closing != expected {
+                    let span = self.span(&token_range);
+                    return Err(MismatchedDelimiter(expected, closing, span).into());
+                }
+                children.push(Node::leaf(NodeKind::Punctuation, token_range));
+                return Ok(Node::inner(kind, children));
+            }
+            let form = self.parse_form(token, token_range)?;
+            children.push(form);
+        }
+    }
+
+    /// Parses `count` forms following the reader macro `text` found at `range`.
+    /// Forms commented out on the way do not count.
+    fn parse_prefixed(
+        &mut self,
+        kind: NodeKind,
+        text: &'static str,
+        range: Range<usize>,
+        count: usize,
+    ) -> Result<Node, Error> {
+        let mut children = vec![Node::leaf(NodeKind::Punctuation, range.clone())];
+        let mut read = 0;
+        while read < count {
+            let (token, token_range) =
+                self.next_token_inside(&mut children, Unclosed::ReaderMacro(text), &range)?;
+            if closing_char(&token).is_some() {
+                return Err(MissingForm(text, self.span(&range)).into());
+            }
+            let form = self.parse_form(token, token_range)?;
+            if form.kind != NodeKind::Discarded {
+                read += 1;
+            }
+            children.push(form);
+        }
+        Ok(Node::inner(kind, children))
+    }
+
+    fn span(&self, range: &Range<usize>) -> Span {
+        self.lines.span(self.source, range)
+    }
+}
+
+/// Offsets at which the lines of a source start, for turning byte ranges into
+/// spans.
+#[derive(Debug, Clone)]
+struct LineStarts(Vec<usize>);
+
+impl LineStarts {
+    fn new(source: &str) -> Self {
+        let newlines = source.match_indices('\n').map(|(newline, _)| newline + 1);
+        LineStarts(std::iter::once(0).chain(newlines).collect())
+    }
+
+    fn span(&self, source: &str, range: &Range<usize>) -> Span {
+        let line = self.0.partition_point(|&start| start <= range.start);
+        let line_start = self.0[line - 1];
+        Span {
+            file: None,
+            line,
+            column: source[line_start..range.start].chars().count() + 1,
+            start: range.start,
+            end: range.end,
+        }
+    }
+}
+
+fn closing_char(token: &SliceToken) -> Option<char> {
+    match token {
+        SliceToken::RightParen => Some(')'),
+        SliceToken::RightBracket => Some(']'),
+        SliceToken::RightBrace => Some('}'),
+        _ => None,
+    }
+}
+
+/// Splits trivia the tokenizer skipped over in `range` into whitespace and
+/// comment leaves. Line comments end before their newline.
+fn push_trivia(source: &str, range: Range<usize>, children: &mut Vec<Node>) {
+    let mut position = range.start;
+    while position < range.end {
+        let rest = &source[position..range.end];
+        let (kind, length) = if rest.starts_with(';') {
+            (NodeKind::Comment, rest.find('\n').unwrap_or(rest.len()))
+        } else if rest.starts_with("#|") {
+            (NodeKind::Comment, block_comment_length(rest))
+        } else {
+            let length = rest
+                .find(|c: char| !(c.is_whitespace() || c == ','))
+                .unwrap_or(rest.len());
+            (NodeKind::Whitespace, length)
+        };
+        children.push(Node::leaf(kind, position..position + length));
+        position += length;
+    }
+}
+
+/// Length of the possibly nested block comment `text` starts with.
+fn block_comment_length(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    let mut depth = 0;
+    let mut position = 0;
+    loop {
+        match (bytes.get(position), bytes.get(position + 1)) {
+            (Some(b'#'), Some(b'|')) => depth += 1,
+            (Some(b'|'), Some(b'#')) => depth -= 1,
+            (Some(_), _) => {
+                position += 1;
+                continue;
+            }
+            (None, _) => return text.len(),
+        }
+        position += 2;
+        if depth == 0 {
+            return position;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reader::{is_incomplete, Reader, ReaderError};
+    use crate::testing::arbitrary_code;
+    use crate::tokenizer::TokenizerError;
+    use proptest::prelude::*;
+
+    const SAMPLE: &str = "; header\n\
+        (defn add [a b] #| block #| nested |# |#\n\
+        \u{20}\u{20}(+ a b)) ; trailing\n\
+        \n\
+        #_ (ignored form)\n\
+        ^:private {:a 1, :b [\"str\\n\" \\space 1/2]}\n\
+        '(quoted ~x ~@xs `y) #{1 2} \n";
+
+    fn leaves<'a>(node: &'a Node, out: &mut Vec<&'a Node>) {
+        if node.children.is_empty() {
+            out.push(node);
+        }
+        node.children.iter().for_each(|child| leaves(child, out));
+    }
+
+    fn reader_forms(code: &str) -> Result<Vec<(Expression, SpanTree)>, Error> {
+        let reader = Reader::from_string(code);
+        let mut forms = vec![];
+        loop {
+            match reader.read_with_spans() {
+                Ok(form) => forms.push(form),
+                Err(error) => {
+                    return match error.downcast_ref::<TokenizerError>() {
+                        Some(TokenizerError::UnexpectedEndOfInput) => Ok(forms),
+                        _ => Err(error),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Debug output shows metadata, which equality of expressions ignores.
+    fn debug(forms: &[(Expression, SpanTree)]) -> String {
+        format!("{:?}", forms)
+    }
+
+    #[test]
+    fn should_print_back_source() -> Result<(), Error> {
+        // given
+        let cst = Cst::parse(SAMPLE)?;
+
+        // expect
+        assert_eq!(SAMPLE, cst.to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn should_cover_source_with_adjacent_leaves() -> Result<(), Error> {
+        // given
+        let cst = Cst::parse(SAMPLE)?;
+        let mut all = vec![];
+
+        // when
+        leaves(cst.root(), &mut all);
+
+        // then
+        let mut position = 0;
+        for leaf in all {
+            assert_eq!(position, leaf.range.start);
+            position = leaf.range.end;
+        }
+        assert_eq!(SAMPLE.len(), position);
+        Ok(())
+    }
+
+    #[test]
+    fn should_keep_trivia_as_leaves() -> Result<(), Error> {
+        // given
+        let cst = Cst::parse("(a ; one\n #| two |# b)")?;
+        let list = &cst.root().children[0];
+
+        // when
+        let kinds: Vec<_> = list
+            .children
+            .iter()
+            .map(|child| (child.kind, cst.text(child)))
+            .collect();
+
+        // then
+        assert_eq!(
+            vec![
+                (NodeKind::Punctuation, "("),
+                (NodeKind::Atom, "a"),
+                (NodeKind::Whitespace, " "),
+                (NodeKind::Comment, "; one"),
+                (NodeKind::Whitespace, "\n "),
+                (NodeKind::Comment, "#| two |#"),
+                (NodeKind::Whitespace, " "),
+                (NodeKind::Atom, "b"),
+                (NodeKind::Punctuation, ")"),
+            ],
+            kinds
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn should_group_reader_macros_with_their_forms() -> Result<(), Error> {
+        // given
+        let cst = Cst::parse("^:a #_ x y")?;
+        let meta = &cst.root().children[0];
+
+        // when
+        let forms: Vec<_> = meta.forms().map(|form| cst.text(form)).collect();
+
+        // then
+        assert_eq!(NodeKind::Meta, meta.kind);
+        assert_eq!("^:a #_ x y", cst.text(meta));
+        assert_eq!(vec![":a", "y"], forms);
+        assert_eq!(NodeKind::Discarded, meta.children[3].kind);
+        Ok(())
+    }
+
+    #[test]
+    fn should_derive_same_expressions_and_spans_as_reader() -> Result<(), Error> {
+        // given
+        let cst = Cst::parse(SAMPLE)?;
+
+        // when
+        let forms = cst.read_with(&TagRegistry::new())?;
+
+        // then
+        assert_eq!(debug(&reader_forms(SAMPLE)?), debug(&forms));
+        assert_eq!(Reader::from_string(SAMPLE).read_all()?, cst.expressions()?);
+        Ok(())
+    }
+
+    #[test]
+    fn should_read_tagged_literals_with_registered_handlers() -> Result<(), Error> {
+        // given
+        let tags = TagRegistry::new().with_tag("twice", |form| match form {
+            Expression::Integer(n) => Ok(Expression::Integer(2 * n)),
+            _ => Err(ReaderError::InvalidValue("not an integer".to_owned())),
+        });
+        let cst = Cst::parse("[#twice 21]")?;
+
+        // when
+        let forms = cst.read_with(&tags)?;
+
+        // then
+        let reader = Reader::from_string("[#twice 21]").with_tags(tags);
+        assert_eq!(reader.read()?, forms[0].0);
+        assert_eq!(Reader::from_string("[42]").read()?, forms[0].0);
+        assert!(cst.expressions().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn should_report_same_syntax_errors_as_reader() {
+        for code in &[
+            "(a ]", "(a", "a)", "'", "(#_)", "^:a", "\"open", "#| open", "1.2.3",
+        ] {
+            // when
+            let error = Cst::parse(code).err().unwrap();
+
+            // then
+            let expected = Reader::from_string(code).read_all().err().unwrap();
+            assert_eq!(expected.to_string(), error.to_string(), "{}", code);
+            assert_eq!(is_incomplete(&expected), is_incomplete(&error), "{}", code);
+        }
+    }
+
+    const ALPHABET: &[&str] = &[
+        "(", ")", "[", "]", "{", "}", "#{", "'", "`", "~", "~@", "^", "#_", "#tag", ":k", "x", "1",
+        "2.5", "1/2", "\"s\\n\"", "\\a", "; c\n", "#| b |#", " ", ",", "\n",
+    ];
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(20_000))]
+
+        #[test]
+        fn should_agree_with_reader_on_arbitrary_input(code in arbitrary_code(ALPHABET)) {
+            // when
+            let cst = Cst::parse(&code);
+
+            // then
+            if let Ok(cst) = &cst {
+                prop_assert_eq!(&code, &cst.to_string());
+            }
+            let expected = reader_forms(&code);
+            let derived = cst.and_then(|cst| cst.read_with(&TagRegistry::new()));
+            match (expected, derived) {
+                (Ok(expected), Ok(derived)) => {
+                    prop_assert_eq!(debug(&expected), debug(&derived), "{}", code)
+                }
+                (Err(_), Err(_)) => {}
+                (expected, derived) => prop_assert!(
+                    false,
+                    "{:?}: reader gave {:?}, CST gave {:?}",
+                    code,
+                    expected.map(|_| ()),
+                    derived.map(|_| ())
+                ),
+            }
+        }
+    }
+}
@@ -0,0 +1,818 @@
+use super::numeric::{self, NumericError};
+use super::{is_truthy, EvalError, Scope};
+use crate::collections::{Map, Set};
+use crate::number::decimal::{RoundingMode, MAX_SCALE};
+use crate::printer::{self, PrettyOptions};
+use crate::reader::{Expression, Function, NativeFn, ScopedFn};
+use failure::Error;
+use std::convert::TryFrom;
+
+#[derive(Debug, Fail)]
+pub enum BuiltinError {
+    #[fail(display = "{} expects at least {} argument(s)", _0, _1)]
+    NotEnoughArguments(&'static str, usize),
+
+    #[fail(display = "{} expects {} but got {}", _0, _1, _2)]
+    WrongType(&'static str, &'static str, &'static str),
+
+    #[fail(display = "{}: {}", _0, _1)]
+    InvalidArgument(&'static str, String),
+}
+
+/// Puts all built-in functions into the scope.
+pub fn register_builtins(scope: &mut Scope) {
+    let natives: &[(&str, NativeFn)] = &[
+        ("+", add),
+        ("-", subtract),
+        ("*", multiply),
+        ("/", divide),
+        ("numerator", numerator),
+        ("denominator", denominator),
+        ("rationalize", rationalize),
+        ("decimal", decimal),
+        ("decimal-div", decimal_div),
+        ("float", float),
+        ("integer", integer),
+        ("not", not),
+        ("char", char),
+        ("char-code", char_code),
+        ("chars", chars),
+        ("string", string),
+        ("pr-str", pr_str),
+        ("print-str", print_str),
+        ("conj", conj),
+        ("disj", disj),
+        ("contains?", contains),
+        ("union", union),
+        ("intersection", intersection),
+        ("difference", difference),
+        ("assoc", assoc),
+        ("meta", meta),
+        ("with-meta", with_meta),
+        ("vary-meta", vary_meta),
+    ];
+    for (name, func) in natives {
+        scope.put(name, Expression::Fn(Function::Native(*func)));
+    }
+    // a child, so the functions do not keep the global scope holding them alive
+    let scoped: &[(&str, ScopedFn)] = &[("pprint", pprint)];
+    for (name, func) in scoped {
+        let function = Function::Scoped(*func, scope.child());
+        scope.put(name, Expression::Fn(function));
+    }
+    for name in &["*print-length*", "*print-level*"] {
+        scope.put(name, Expression::Nil);
+    }
+}
+
+/// Options of the pretty printer set by `*print-length*` and `*print-level*` in
+/// `scope`, for printing results in the REPL.
+pub fn print_options(scope: &Scope) -> PrettyOptions {
+    let limit = |name| match scope.get(name) {
+        Ok(Expression::Integer(value)) => usize::try_from(value).ok(),
+        _ => None,
+    };
+    PrettyOptions {
+        print_length: limit("*print-length*"),
+        print_level: limit("*print-level*"),
+        ..PrettyOptions::default()
+    }
+}
+
+fn add(args: &[Expression]) -> Result<Expression, Error> {
+    reduce(Expression::Integer(0), args, numeric::add)
+}
+
+fn subtract(args: &[Expression]) -> Result<Expression, Error> {
+    match args {
+        [] => Err(BuiltinError::NotEnoughArguments("-", 1).into()),
+        [value] => numeric::negate(value),
+        [first, rest @ ..] => fold(first.clone(), rest, numeric::subtract),
+    }
+}
+
+fn multiply(args: &[Expression]) -> Result<Expression, Error> {
+    reduce(Expression::Integer(1), args, numeric::multiply)
+}
+
+fn divide(args: &[Expression]) -> Result<Expression, Error> {
+    match args {
+        [] => Err(BuiltinError::NotEnoughArguments("/", 1).into()),
+        [value] => numeric::reciprocal(value),
+        [first, rest @ ..] => fold(first.clone(), rest, numeric::divide),
+    }
+}
+
+fn numerator(args: &[Expression]) -> Result<Expression, Error> {
+    numeric::numerator(single("numerator", args)?)
+}
+
+fn denominator(args: &[Expression]) -> Result<Expression, Error> {
+    numeric::denominator(single("denominator", args)?)
+}
+
+fn rationalize(args: &[Expression]) -> Result<Expression, Error> {
+    numeric::rationalize(single("rationalize", args)?)
+}
+
+/// `(decimal x)` converts exactly, `(decimal x scale rounding-mode)` rounds.
+fn decimal(args: &[Expression]) -> Result<Expression, Error> {
+    match args {
+        [value] => numeric::to_decimal(value, None),
+        [value, scale, mode] => {
+            numeric::to_decimal(value, Some((scale_arg(scale)?, rounding_mode_arg(mode)?)))
+        }
+        _ => Err(EvalError::WrongArity("decimal".into(), "1 or 3".into(), args.len()).into()),
+    }
+}
+
+/// `(decimal-div a b scale rounding-mode)`
+fn decimal_div(args: &[Expression]) -> Result<Expression, Error> {
+    match args {
+        [a, b, scale, mode] => {
+            numeric::divide_decimals(a, b, scale_arg(scale)?, rounding_mode_arg(mode)?)
+        }
+        _ => Err(EvalError::WrongArity("decimal-div".into(), "4".into(), args.len()).into()),
+    }
+}
+
+fn float(args: &[Expression]) -> Result<Expression, Error> {
+    numeric::to_float(single("float", args)?)
+}
+
+fn integer(args: &[Expression]) -> Result<Expression, Error> {
+    numeric::to_integer(single("integer", args)?)
+}
+
+fn not(args: &[Expression]) -> Result<Expression, Error> {
+    Ok(Expression::Bool(!is_truthy(single("not", args)?)))
+}
+
+/// `(char 233)` or `(char "é")`
+fn char(args: &[Expression]) -> Result<Expression, Error> {
+    match single("char", args)? {
+        Expression::Integer(code) => u32::try_from(*code)
+            .ok()
+            .and_then(std::char::from_u32)
+            .map(Expression::Char)
+            .ok_or_else(|| {
+                let message = format!("{} is not a unicode scalar value", code);
+                BuiltinError::InvalidArgument("char", message).into()
+            }),
+        Expression::String(text) => {
+            let mut chars = text.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(Expression::Char(c)),
+                _ => {
+                    let message = format!("{:?} is not a single character", text);
+                    Err(BuiltinError::InvalidArgument("char", message).into())
+                }
+            }
+        }
+        other => {
+            Err(
+                BuiltinError::WrongType("char", "a code point or a string", other.type_name())
+                    .into(),
+            )
+        }
+    }
+}
+
+fn char_code(args: &[Expression]) -> Result<Expression, Error> {
+    match single("char-code", args)? {
+        Expression::Char(c) => Ok(Expression::Integer(i64::from(u32::from(*c)))),
+        other => Err(BuiltinError::WrongType("char-code", "a char", other.type_name()).into()),
+    }
+}
+
+/// Characters of a string, as a vector.
+fn chars(args: &[Expression]) -> Result<Expression, Error> {
+    match single("chars", args)? {
+        Expression::String(text) => Ok(Expression::Vector(
+            text.chars().map(Expression::Char).collect(),
+        )),
+        other => Err(BuiltinError::WrongType("chars", "a string", other.type_name()).into()),
+    }
+}
+
+/// Joins characters and strings into a string.
+fn string(args: &[Expression]) -> Result<Expression, Error> {
+    let mut result = String::new();
+    for arg in args {
+        match arg {
+            Expression::Char(c) => result.push(*c),
+            Expression::String(text) => result.push_str(text),
+            other => {
+                return Err(BuiltinError::WrongType(
+                    "string",
+                    "chars or strings",
+                    other.type_name(),
+                )
+                .into())
+            }
+        }
+    }
+    Ok(Expression::String(result))
+}
+
+/// Arguments printed readably and separated by spaces.
+fn pr_str(args: &[Expression]) -> Result<Expression, Error> {
+    Ok(print_all(args, printer::pr_str))
+}
+
+/// Arguments printed for people to read and separated by spaces.
+fn print_str(args: &[Expression]) -> Result<Expression, Error> {
+    Ok(print_all(args, printer::print_str))
+}
+
+fn print_all(args: &[Expression], print: fn(&Expression) -> String) -> Expression {
+    let printed: Vec<String> = args.iter().map(print).collect();
+    Expression::String(printed.join(" "))
+}
+
+/// `(pprint value)` or `(pprint value {:width 40 :length 10 :level 3})`, pretty
+/// prints the value to standard output.
+fn pprint(scope: &Scope, args: &[Expression]) -> Result<Expression, Error> {
+    println!("{}", pprinted(scope, args)?);
+    Ok(Expression::Nil)
+}
+
+/// Text printed by `pprint`. Limits not given in the options are taken from
+/// `*print-length*` and `*print-level*`.
+fn pprinted(scope: &Scope, args: &[Expression]) -> Result<String, Error> {
+    let options = match args {
+        [_] => print_options(scope),
+        [_, options] => pretty_options_arg(options, print_options(scope))?,
+        _ => {
+            return Err(EvalError::WrongArity("pprint".into(), "1 or 2".into(), args.len()).into())
+        }
+    };
+    Ok(printer::pprint_str(&args[0], &options))
+}
+
+/// Adds elements to a set, or to the end of a vector.
+fn conj(args: &[Expression]) -> Result<Expression, Error> {
+    match args {
+        [] => Err(BuiltinError::NotEnoughArguments("conj", 1).into()),
+        [target, elements @ ..] => match target.without_meta() {
+            Expression::Set(set) => {
+                let mut set = set.clone();
+                for element in elements {
+                    set.insert(element.clone());
+                }
+                Ok(keep_meta(target, Expression::Set(set)))
+            }
+            Expression::Vector(values) => {
+                let values = values.iter().chain(elements).cloned().collect();
+                Ok(keep_meta(target, Expression::Vector(values)))
+            }
+            other => {
+                Err(BuiltinError::WrongType("conj", "a set or a vector", other.type_name()).into())
+            }
+        },
+    }
+}
+
+fn disj(args: &[Expression]) -> Result<Expression, Error> {
+    match args {
+        [] => Err(BuiltinError::NotEnoughArguments("disj", 1).into()),
+        [set, elements @ ..] => {
+            let mut result = set_arg("disj", set)?.clone();
+            for element in elements {
+                result.remove(element);
+            }
+            Ok(keep_meta(set, Expression::Set(result)))
+        }
+    }
+}
+
+/// Whether a set has the element, or a map has the key.
+fn contains(args: &[Expression]) -> Result<Expression, Error> {
+    match args {
+        [collection, element] => match collection.without_meta() {
+            Expression::Set(set) => Ok(Expression::Bool(set.contains(element))),
+            Expression::Map(map) => Ok(Expression::Bool(map.contains_key(element))),
+            other => Err(
+                BuiltinError::WrongType("contains?", "a set or a map", other.type_name()).into(),
+            ),
+        },
+        _ => Err(EvalError::WrongArity("contains?".into(), "2".into(), args.len()).into()),
+    }
+}
+
+/// `(assoc map key value ...)`, where `nil` stands for an empty map.
+fn assoc(args: &[Expression]) -> Result<Expression, Error> {
+    match args {
+        [target, entries @ ..] if !entries.is_empty() && entries.len() % 2 == 0 => {
+            let mut map = match target.without_meta() {
+                Expression::Map(map) => map.clone(),
+                Expression::Nil => Map::new(),
+                other => {
+                    return Err(BuiltinError::WrongType("assoc", "a map", other.type_name()).into())
+                }
+            };
+            for entry in entries.chunks(2) {
+                map.insert(entry[0].clone(), entry[1].clone());
+            }
+            Ok(keep_meta(target, Expression::Map(map)))
+        }
+        _ => Err(BuiltinError::InvalidArgument(
+            "assoc",
+            "expects a map followed by keys and values".to_owned(),
+        )
+        .into()),
+    }
+}
+
+/// Metadata of the argument, or `nil` when it has none.
+fn meta(args: &[Expression]) -> Result<Expression, Error> {
+    Ok(match single("meta", args)?.meta() {
+        Some(meta) => Expression::Map(meta.clone()),
+        None => Expression::Nil,
+    })
+}
+
+/// `(with-meta form metadata)`, where `metadata` is a map or `nil`.
+fn with_meta(args: &[Expression]) -> Result<Expression, Error> {
+    match args {
+        [form, metadata] => attach_meta("with-meta", form, metadata),
+        _ => Err(EvalError::WrongArity("with-meta".into(), "2".into(), args.len()).into()),
+    }
+}
+
+/// `(vary-meta form f args...)` replaces metadata with `(f metadata args...)`.
+fn vary_meta(args: &[Expression]) -> Result<Expression, Error> {
+    match args {
+        [form, Expression::Fn(function), rest @ ..] => {
+            let current = meta(&args[..1])?;
+            let call_args: Vec<_> = std::iter::once(current)
+                .chain(rest.iter().cloned())
+                .collect();
+            attach_meta("vary-meta", form, &function.call(&call_args)?)
+        }
+        [_, other, ..] => {
+            Err(BuiltinError::WrongType("vary-meta", "a function", other.type_name()).into())
+        }
+        _ => Err(BuiltinError::NotEnoughArguments("vary-meta", 2).into()),
+    }
+}
+
+fn attach_meta(
+    name: &'static str,
+    form: &Expression,
+    metadata: &Expression,
+) -> Result<Expression, Error> {
+    if !form.can_have_meta() {
+        return Err(BuiltinError::WrongType(
+            name,
+            "an identifier or a collection",
+            form.type_name(),
+        )
+        .into());
+    }
+    match metadata {
+        Expression::Map(map) => Ok(form.clone().with_meta(map.clone())),
+        Expression::Nil => Ok(form.clone().with_meta(Map::new())),
+        other => Err(BuiltinError::WrongType(name, "a map or nil", other.type_name()).into()),
+    }
+}
+
+/// `result` with the metadata of `original`.
+fn keep_meta(original: &Expression, result: Expression) -> Expression {
+    match original.meta() {
+        Some(meta) => result.with_meta(meta.clone()),
+        None => result,
+    }
+}
+
+fn union(args: &[Expression]) -> Result<Expression, Error> {
+    combine_sets("union", args, Set::new(), Set::union)
+}
+
+fn intersection(args: &[Expression]) -> Result<Expression, Error> {
+    match args {
+        [] => Err(BuiltinError::NotEnoughArguments("intersection", 1).into()),
+        [first, rest @ ..] => combine_sets(
+            "intersection",
+            rest,
+            set_arg("intersection", first)?.clone(),
+            Set::intersection,
+        ),
+    }
+}
+
+fn difference(args: &[Expression]) -> Result<Expression, Error> {
+    match args {
+        [] => Err(BuiltinError::NotEnoughArguments("difference", 1).into()),
+        [first, rest @ ..] => combine_sets(
+            "difference",
+            rest,
+            set_arg("difference", first)?.clone(),
+            Set::difference,
+        ),
+    }
+}
+
+fn combine_sets(
+    name: &'static str,
+    args: &[Expression],
+    init: Set,
+    op: fn(&Set, &Set) -> Set,
+) -> Result<Expression, Error> {
+    args.iter()
+        .try_fold(init, |acc, arg| Ok(op(&acc, set_arg(name, arg)?)))
+        .map(Expression::Set)
+}
+
+fn set_arg<'a>(name: &'static str, expr: &'a Expression) -> Result<&'a Set, Error> {
+    match expr.without_meta() {
+        Expression::Set(set) => Ok(set),
+        other => Err(BuiltinError::WrongType(name, "a set", other.type_name()).into()),
+    }
+}
+
+fn scale_arg(expr: &Expression) -> Result<u32, Error> {
+    match expr {
+        Expression::Integer(value) if *value > i64::from(MAX_SCALE) => {
+            Err(NumericError::ScaleTooLarge(*value, MAX_SCALE).into())
+        }
+        Expression::Integer(value) if *value >= 0 => Ok(*value as u32),
+        other => {
+            Err(NumericError::UnexpectedType("a non-negative scale", other.type_name()).into())
+        }
+    }
+}
+
+fn rounding_mode_arg(expr: &Expression) -> Result<RoundingMode, Error> {
+    match expr {
+        Expression::String(name) => Ok(name.parse()?),
+        other => {
+            Err(NumericError::UnexpectedType("a rounding mode name", other.type_name()).into())
+        }
+    }
+}
+
+/// `options` with the entries of the map `expr` applied.
+fn pretty_options_arg(
+    expr: &Expression,
+    mut options: PrettyOptions,
+) -> Result<PrettyOptions, Error> {
+    let map = match expr.without_meta() {
+        Expression::Map(map) => map,
+        other => {
+            return Err(
+                BuiltinError::WrongType("pprint", "a map of options", other.type_name()).into(),
+            )
+        }
+    };
+    for (key, value) in map.iter() {
+        let number = match value {
+            Expression::Nil => None,
+            Expression::Integer(number) if *number >= 0 => Some(*number as usize),
+            _ => {
+                let message = format!("{} is not a valid {}", printer::pr_str(value), key);
+                return Err(BuiltinError::InvalidArgument("pprint", message).into());
+            }
+        };
+        match key {
+            Expression::Keyword(name) if name == "width" => {
+                options.width = number.unwrap_or(options.width)
+            }
+            Expression::Keyword(name) if name == "length" => options.print_length = number,
+            Expression::Keyword(name) if name == "level" => options.print_level = number,
+            _ => {
+                let message = format!("unknown option {}", printer::pr_str(key));
+                return Err(BuiltinError::InvalidArgument("pprint", message).into());
+            }
+        }
+    }
+    Ok(options)
+}
+
+fn single<'a>(name: &'static str, args: &'a [Expression]) -> Result<&'a Expression, Error> {
+    match args {
+        [arg] => Ok(arg),
+        _ => Err(EvalError::WrongArity(name.into(), "1".into(), args.len()).into()),
+    }
+}
+
+/// Folds arguments with `op`, returning `identity` when there are none.
+fn reduce(
+    identity: Expression,
+    args: &[Expression],
+    op: fn(&Expression, &Expression) -> Result<Expression, Error>,
+) -> Result<Expression, Error> {
+    match args {
+        [] => Ok(identity),
+        [first, rest @ ..] => {
+            numeric::ensure_number(first)?;
+            fold(first.clone(), rest, op)
+        }
+    }
+}
+
+fn fold(
+    init: Expression,
+    args: &[Expression],
+    op: fn(&Expression, &Expression) -> Result<Expression, Error>,
+) -> Result<Expression, Error> {
+    args.iter().try_fold(init, |acc, arg| op(&acc, arg))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::eval::eval;
+    use crate::reader::Reader;
+
+    fn eval_str(code: &str) -> Result<Expression, Error> {
+        let mut scope = Scope::new();
+        register_builtins(&mut scope);
+        Ok(eval(&mut scope, &Reader::from_string(code).read()?)?)
+    }
+
+    #[test]
+    fn should_do_arithmetic() -> Result<(), Error> {
+        // expect
+        assert_eq!(Expression::Integer(0), eval_str("(+)")?);
+        assert_eq!(Expression::Integer(6), eval_str("(+ 1 2 3)")?);
+        assert_eq!(Expression::Integer(-4), eval_str("(- 4)")?);
+        assert_eq!(Expression::Integer(4), eval_str("(- 10 4 2)")?);
+        assert_eq!(Expression::Integer(24), eval_str("(* 2 3 4)")?);
+        assert_eq!(Expression::Float(4.5), eval_str("(+ 1 1.5 2)")?);
+        Ok(())
+    }
+
+    #[test]
+    fn should_promote_literals_and_results_beyond_i64() -> Result<(), Error> {
+        // expect
+        assert_eq!(
+            eval_str("18446744073709551616")?,
+            eval_str("(* 4294967296 4294967296)")?
+        );
+        assert_eq!(
+            Expression::Integer(1),
+            eval_str("(- 9223372036854775808 9223372036854775807)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn should_keep_division_exact() -> Result<(), Error> {
+        // expect
+        assert_eq!(eval_str("1/3")?, eval_str("(/ 1 3)")?);
+        assert_eq!(eval_str("1/3")?, eval_str("(/ 3)")?);
+        assert_eq!(Expression::Integer(1), eval_str("(* (/ 1 3) 3)")?);
+        assert_eq!(Expression::Integer(2), eval_str("(/ 12 3 2)")?);
+        assert_eq!(Expression::Float(0.5), eval_str("(/ 1 2.0)")?);
+        assert_eq!(Expression::Integer(-3), eval_str("(numerator -3/4)")?);
+        assert_eq!(Expression::Integer(4), eval_str("(denominator -3/4)")?);
+        assert_eq!(eval_str("3/20")?, eval_str("(rationalize 0.15)")?);
+        assert!(eval_str("(/ 1 0)").is_err());
+        assert!(eval_str("(numerator 1 2)").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn should_compute_with_decimals() -> Result<(), Error> {
+        // expect
+        assert_eq!("25.00M", eval_str("(+ 12.50M 12.50M)")?.to_string());
+        assert_eq!("3.7500M", eval_str("(* 1.25M 3.00M)")?.to_string());
+        assert_eq!("-1.5M", eval_str("(- 1.5M)")?.to_string());
+        assert_eq!("0.5M", eval_str("(/ 2M)")?.to_string());
+        assert_eq!("2.50M", eval_str("(/ 10.00M 4M)")?.to_string());
+        assert!(eval_str("(/ 10.00M 3M)").is_err());
+        assert!(eval_str("(+ 1.00M 1)").is_err());
+        assert!(eval_str("(+ \"a\")").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn should_divide_decimals_with_scale_and_rounding() -> Result<(), Error> {
+        // expect
+        assert_eq!(
+            "3.33M",
+            eval_str("(decimal-div 10.00M 3M 2 \"half-even\")")?.to_string()
+        );
+        assert_eq!(
+            "0.6M",
+            eval_str("(decimal-div 2M 3M 1 \"down\")")?.to_string()
+        );
+        assert!(eval_str("(decimal-div 2M 3M 1 \"sideways\")").is_err());
+        assert!(eval_str("(decimal-div 2M 3 1 \"down\")").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn should_convert_decimals_explicitly() -> Result<(), Error> {
+        // expect
+        assert_eq!("3M", eval_str("(decimal 3)")?.to_string());
+        assert_eq!("0.125M", eval_str("(decimal 1/8)")?.to_string());
+        assert_eq!(
+            "0.33M",
+            eval_str("(decimal 1/3 2 \"half-up\")")?.to_string()
+        );
+        assert_eq!("0.1M", eval_str("(decimal 0.1)")?.to_string());
+        assert_eq!(
+            "12.35M",
+            eval_str("(decimal 12.345M 2 \"half-up\")")?.to_string()
+        );
+        assert_eq!(eval_str("5/2")?, eval_str("(rationalize 2.50M)")?);
+        assert_eq!(Expression::Float(2.5), eval_str("(float 2.50M)")?);
+        assert_eq!(Expression::Integer(2), eval_str("(integer 2.50M)")?);
+        assert!(eval_str("(decimal 1/3)").is_err());
+        assert_eq!(
+            "decimal expects 1 or 3 argument(s) but got 2",
+            decimal(&[Expression::Integer(1), Expression::Integer(2)])
+                .err()
+                .unwrap()
+                .to_string()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn should_reject_scales_above_maximum() {
+        // when
+        let error = eval_str("(decimal 1 4000000000 \"half-up\")")
+            .err()
+            .unwrap();
+
+        // then
+        assert!(error
+            .to_string()
+            .ends_with("Scale 4000000000 is larger than the maximum of 1000"));
+        assert!(eval_str("(decimal-div 1M 3M 1001 \"down\")").is_err());
+        assert!(eval_str("(decimal 1 1000 \"down\")").is_ok());
+    }
+
+    #[test]
+    fn should_negate_truthiness() -> Result<(), Error> {
+        // expect
+        assert_eq!(Expression::Bool(true), eval_str("(not nil)")?);
+        assert_eq!(Expression::Bool(true), eval_str("(not false)")?);
+        assert_eq!(Expression::Bool(false), eval_str("(not 0)")?);
+        assert_eq!(Expression::Bool(false), eval_str("(not :key)")?);
+        Ok(())
+    }
+
+    #[test]
+    fn should_convert_between_chars_code_points_and_strings() -> Result<(), Error> {
+        // expect
+        assert_eq!(Expression::Char('é'), eval_str("(char 233)")?);
+        assert_eq!(Expression::Char('é'), eval_str("(char \"é\")")?);
+        assert_eq!(Expression::Integer(233), eval_str("(char-code \\é)")?);
+        assert_eq!(Expression::Integer(10), eval_str("(char-code \\newline)")?);
+        assert_eq!(
+            Expression::String("hé!".to_owned()),
+            eval_str("(string \\h \"é\" \\!)")?
+        );
+        assert_eq!(eval_str("[\\a \\ż]")?, eval_str("(chars \"aż\")")?);
+        assert_eq!(Expression::String(String::new()), eval_str("(string)")?);
+        assert!(eval_str("(char 55296)").is_err());
+        assert!(eval_str("(char -1)").is_err());
+        assert!(eval_str("(char \"ab\")").is_err());
+        assert!(eval_str("(char-code \"a\")").is_err());
+        assert!(eval_str("(string 1)").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn should_print_values_to_strings() -> Result<(), Error> {
+        // expect
+        assert_eq!(
+            Expression::String("\"a b\" \\c [1 :d]".to_owned()),
+            eval_str("(pr-str \"a b\" \\c [1 :d])")?
+        );
+        assert_eq!(
+            Expression::String("a b c [1 :d]".to_owned()),
+            eval_str("(print-str \"a b\" \\c [1 :d])")?
+        );
+        assert_eq!(Expression::String(String::new()), eval_str("(pr-str)")?);
+        Ok(())
+    }
+
+    #[test]
+    fn should_read_pretty_printer_options() -> Result<(), Error> {
+        // given
+        let mut scope = Scope::new();
+        register_builtins(&mut scope);
+        scope.put(&"*print-length*", Expression::Integer(10));
+
+        // when
+        let from_scope = print_options(&scope);
+        let map = eval_str("{:width 40 :level 2 :length nil}")?;
+        let from_map = pretty_options_arg(&map, from_scope.clone())?;
+
+        // then
+        assert_eq!(Some(10), from_scope.print_length);
+        assert_eq!(None, from_scope.print_level);
+        assert_eq!(40, from_map.width);
+        assert_eq!(Some(2), from_map.print_level);
+        assert_eq!(None, from_map.print_length);
+        assert_eq!(Expression::Nil, eval_str("(pprint [1 2] {:width 10})")?);
+        assert!(eval_str("(pprint [1] {:depth 1})").is_err());
+        assert!(eval_str("(pprint [1] {:level -1})").is_err());
+        assert_eq!(
+            "pprint expects 1 or 2 argument(s) but got 0",
+            pprinted(&scope, &[]).err().unwrap().to_string()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn should_limit_pprint_by_print_vars() -> Result<(), Error> {
+        // given
+        let mut scope = Scope::new();
+        register_builtins(&mut scope);
+        for code in &["(def *print-length* 2)", "(def *print-level* 2)"] {
+            eval(&mut scope, &Reader::from_string(code).read()?)?;
+        }
+        let read = |code| Reader::from_string(code).read();
+
+        // when
+        let limited = pprinted(&scope, &[read("[[1 [2]] 3 4]")?])?;
+        let overridden = pprinted(&scope, &[read("[1 2 3]")?, read("{:length nil}")?])?;
+
+        // then
+        assert_eq!("[[1 #] 3 ...]", limited);
+        assert_eq!("[1 2 3]", overridden);
+        Ok(())
+    }
+
+    #[test]
+    fn should_operate_on_sets() -> Result<(), Error> {
+        // expect
+        assert_eq!(eval_str("#{1 2 3}")?, eval_str("(conj #{1 2} 3 1)")?);
+        assert_eq!(eval_str("[1 2 3]")?, eval_str("(conj [1] 2 3)")?);
+        assert_eq!(eval_str("#{2}")?, eval_str("(disj #{1 2 3} 1 3 4)")?);
+        assert_eq!(Expression::Bool(true), eval_str("(contains? #{:a :b} :a)")?);
+        assert_eq!(
+            Expression::Bool(false),
+            eval_str("(contains? #{:a :b} :c)")?
+        );
+        assert_eq!(Expression::Bool(true), eval_str("(contains? {:a 1} :a)")?);
+        assert_eq!(eval_str("#{1 2 3}")?, eval_str("(union #{1} #{2 3} #{3})")?);
+        assert_eq!(eval_str("#{}")?, eval_str("(union)")?);
+        assert_eq!(
+            eval_str("#{2}")?,
+            eval_str("(intersection #{1 2} #{2 3} #{2})")?
+        );
+        assert_eq!(
+            eval_str("#{1}")?,
+            eval_str("(difference #{1 2 3} #{2} #{3})")?
+        );
+        assert!(eval_str("(union #{1} [2])").is_err());
+        assert!(eval_str("(contains? [1] 1)").is_err());
+        assert!(eval_str("(difference)").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn should_read_and_change_metadata() -> Result<(), Error> {
+        // expect
+        assert_eq!(
+            eval_str("{:doc \"v\"}")?,
+            eval_str("(meta ^{:doc \"v\"} [1])")?
+        );
+        assert_eq!(Expression::Nil, eval_str("(meta [1])")?);
+        assert_eq!(
+            eval_str("{:a 1}")?,
+            eval_str("(meta (with-meta [1] {:a 1}))")?
+        );
+        assert_eq!(Expression::Nil, eval_str("(meta (with-meta ^:a [1] nil))")?);
+        assert_eq!(
+            eval_str("{:a true :b 2}")?,
+            eval_str("(meta (vary-meta ^:a #{} assoc :b 2))")?
+        );
+        assert_eq!(
+            eval_str("{:b 2}")?,
+            eval_str("(meta (vary-meta [] assoc :b 2))")?
+        );
+        assert_eq!(eval_str("{:a true}")?, eval_str("(meta (conj ^:a [1] 2))")?);
+        assert_eq!(eval_str("[1 2]")?, eval_str("(conj ^:a [1] 2)")?);
+        assert!(eval_str("(with-meta 1 {:a 1})").is_err());
+        assert!(eval_str("(with-meta [] [1])").is_err());
+        assert!(eval_str("(vary-meta [] 1)").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn should_associate_map_entries() -> Result<(), Error> {
+        // expect
+        assert_eq!(
+            eval_str("{:a 1 :b 3}")?,
+            eval_str("(assoc {:a 1 :b 2} :b 3)")?
+        );
+        assert_eq!(eval_str("{:a 1}")?, eval_str("(assoc nil :a 1)")?);
+        assert!(eval_str("(assoc {} :a)").is_err());
+        assert!(eval_str("(assoc [] :a 1)").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn should_fail_to_subtract_without_arguments() {
+        // expect
+        assert!(eval_str("(-)").is_err());
+    }
+}
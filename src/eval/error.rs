@@ -1,15 +1,22 @@
 use super::scope::ScopeError;
-use crate::reader::Expression;
 use failure::Error;
+use std::borrow::Cow;
 
 #[derive(Debug, Fail)]
 pub enum EvalError {
     #[fail(display = "{} is not a function", _0)]
-    NotAFunction(Expression),
+    NotAFunction(String),
 
     #[fail(display = "Empty list is not a valid function call")]
     EmptyList,
 
+    /// Name of the called form or function, expected and actual argument count.
+    #[fail(display = "{} expects {} argument(s) but got {}", _0, _1, _2)]
+    WrongArity(Cow<'static, str>, Cow<'static, str>, usize),
+
+    #[fail(display = "Invalid {} form: {}", _0, _1)]
+    InvalidForm(&'static str, String),
+
     #[fail(display = "Scope error: {}", _0)]
     ScopeError(ScopeError),
 
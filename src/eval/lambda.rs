@@ -0,0 +1,254 @@
+use super::error::EvalError::{self, InvalidForm, WrongArity};
+use super::special_forms::eval_do;
+use super::Scope;
+use crate::reader::{Expression, Function};
+use std::rc::Rc;
+
+/// Function created by `fn`, keeps the scope it was created in so its body sees
+/// the names that were visible there.
+///
+/// It keeps a child of that scope rather than the scope itself, so a function
+/// defined globally refers to the global scope weakly and does not keep it alive.
+pub struct Lambda {
+    name: Option<String>,
+    params: Vec<String>,
+    /// Name after `&` in the parameters, gets the remaining arguments as a list.
+    rest: Option<String>,
+    body: Vec<Expression>,
+    scope: Scope,
+}
+
+impl Lambda {
+    /// Reads `[params...]` of special form `form` and captures `scope`.
+    pub(super) fn new(
+        form: &'static str,
+        name: Option<String>,
+        params: &Expression,
+        body: &[Expression],
+        scope: &Scope,
+    ) -> Result<Lambda, EvalError> {
+        let params = match params.without_meta() {
+            Expression::Vector(params) => params
+                .iter()
+                .map(|param| parameter(form, param))
+                .collect::<Result<Vec<_>, _>>()?,
+            other => {
+                let message = format!("parameters must be a vector, got {}", other.type_name());
+                return Err(InvalidForm(form, message));
+            }
+        };
+        let (params, rest) = match params.iter().position(|param| param == "&") {
+            None => (params, None),
+            Some(index) if index + 2 == params.len() && params[index + 1] != "&" => {
+                let rest = params[index + 1].clone();
+                (params[..index].to_vec(), Some(rest))
+            }
+            Some(_) => {
+                let message = "& must be followed by exactly one name".to_owned();
+                return Err(InvalidForm(form, message));
+            }
+        };
+        Ok(Lambda {
+            name,
+            params,
+            rest,
+            body: body.to_vec(),
+            scope: scope.child(),
+        })
+    }
+
+    /// Evaluates the body with the parameters bound to `args` in a child of the
+    /// captured scope and returns the value of its last form.
+    pub fn call(self: &Rc<Self>, args: &[Expression]) -> Result<Expression, EvalError> {
+        let arity_matches = match self.rest {
+            Some(_) => args.len() >= self.params.len(),
+            None => args.len() == self.params.len(),
+        };
+        if !arity_matches {
+            return Err(WrongArity(
+                self.describe().into(),
+                self.arity().into(),
+                args.len(),
+            ));
+        }
+        let mut scope = self.scope.child();
+        if let Some(name) = &self.name {
+            scope.put(name, Expression::Fn(Function::Regular(self.clone())));
+        }
+        for (param, arg) in self.params.iter().zip(args) {
+            scope.put(param, arg.clone());
+        }
+        if let Some(rest) = &self.rest {
+            let rest_args = args[self.params.len()..].to_vec();
+            scope.put(rest, Expression::List(rest_args));
+        }
+        eval_do(&mut scope, &self.body)
+    }
+
+    fn describe(&self) -> String {
+        self.name.clone().unwrap_or_else(|| "fn".to_owned())
+    }
+
+    fn arity(&self) -> String {
+        match self.rest {
+            Some(_) => format!("at least {}", self.params.len()),
+            None => self.params.len().to_string(),
+        }
+    }
+}
+
+fn parameter(form: &'static str, param: &Expression) -> Result<String, EvalError> {
+    match param.without_meta() {
+        Expression::Identifier(name) => Ok(name.clone()),
+        other => {
+            let message = format!("expected a symbol parameter, got {}", other.type_name());
+            Err(InvalidForm(form, message))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Lambda;
+    use crate::eval::testing::{eval_all, read};
+    use crate::eval::{eval, register_builtins, Scope};
+    use crate::reader::{Expression, Function, Reader};
+    use failure::Error;
+    use std::rc::{Rc, Weak};
+
+    #[test]
+    fn should_bind_parameters_and_return_last_body_form() -> Result<(), Error> {
+        // expect
+        assert_eq!(
+            Expression::Integer(3),
+            eval_all("((fn [a b] (+ a b)) 1 2)")?
+        );
+        assert_eq!(
+            Expression::Integer(2),
+            eval_all("((lambda [a] a (+ a 1)) 1)")?
+        );
+        assert_eq!(Expression::Nil, eval_all("((fn []))")?);
+        assert_eq!(
+            Expression::Integer(1),
+            eval_all("(def a 1) ((fn [a] a) 2) a")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn should_capture_lexical_scope() -> Result<(), Error> {
+        // expect
+        assert_eq!(
+            Expression::Integer(15),
+            eval_all("(def add (let [n 5] (fn [x] (+ x n)))) (let [n 100] (add 10))")?
+        );
+        assert_eq!(
+            Expression::Integer(7),
+            eval_all("(def adder (fn [n] (fn [x] (+ x n)))) ((adder 3) 4)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn should_define_functions_that_call_themselves() -> Result<(), Error> {
+        // expect
+        assert_eq!(
+            Expression::Integer(120),
+            eval_all("(defn fact [n] (if (contains? #{0 1} n) 1 (* n (fact (- n 1))))) (fact 5)")?
+        );
+        assert_eq!(
+            Expression::Integer(6),
+            eval_all("((fn sum [n] (if (contains? #{0} n) 0 (+ n (sum (- n 1))))) 3)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn should_collect_rest_arguments_into_list() -> Result<(), Error> {
+        // expect
+        assert_eq!(
+            read("[1 (2 3)]"),
+            eval_all("((fn [a & more] [a more]) 1 2 3)")?
+        );
+        assert_eq!(read("()"), eval_all("((fn [& more] more))")?);
+        Ok(())
+    }
+
+    #[test]
+    fn should_be_callable_from_native_functions() -> Result<(), Error> {
+        // expect
+        assert_eq!(
+            read("{:seen true}"),
+            eval_all("(meta (vary-meta [] (fn [m k] (assoc m k true)) :seen))")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn should_free_functions_stored_in_scopes_they_capture() -> Result<(), Error> {
+        // given
+        let mut scope = Scope::new();
+        register_builtins(&mut scope);
+        let code = "(defn f [x] (f x)) (def g (let [n 1] (fn [] n))) (let [h (fn [] 1)] (def h h))";
+        for expr in Reader::from_string(code) {
+            eval(&mut scope, &expr?)?;
+        }
+        let lambdas: Vec<Weak<Lambda>> = ["f", "g", "h"]
+            .iter()
+            .map(|name| match scope.get(name) {
+                Ok(Expression::Fn(Function::Regular(lambda))) => Rc::downgrade(&lambda),
+                other => panic!("{} is not a function: {:?}", name, other.ok()),
+            })
+            .collect();
+
+        // when
+        drop(scope);
+
+        // then
+        assert!(lambdas.iter().all(|lambda| lambda.upgrade().is_none()));
+        Ok(())
+    }
+
+    #[test]
+    fn should_report_arity_mismatch_and_malformed_functions() {
+        // given
+        let cases = [
+            ("((fn [a] a))", "fn expects 1 argument(s) but got 0"),
+            (
+                "(defn f [a b] a) (f 1 2 3)",
+                "f expects 2 argument(s) but got 3",
+            ),
+            (
+                "((fn [a & more] a))",
+                "fn expects at least 1 argument(s) but got 0",
+            ),
+            ("(fn)", "fn expects at least 1 argument(s) but got 0"),
+            ("(defn f)", "defn expects at least 2 argument(s) but got 1"),
+            (
+                "(fn (a) a)",
+                "Invalid fn form: parameters must be a vector, got list",
+            ),
+            (
+                "(lambda [1] 1)",
+                "Invalid lambda form: expected a symbol parameter, got integer",
+            ),
+            (
+                "(fn [a &] a)",
+                "Invalid fn form: & must be followed by exactly one name",
+            ),
+            (
+                "(defn 1 [] 1)",
+                "Invalid defn form: expected a symbol to bind, got integer",
+            ),
+            ("(1 2)", "1 is not a function"),
+        ];
+
+        for (code, message) in &cases {
+            // when
+            let error = eval_all(code).err().unwrap();
+
+            // then
+            assert_eq!(*message, error.to_string(), "{}", code);
+        }
+    }
+}
@@ -1,38 +1,104 @@
-use super::reader::Expression;
+use super::reader::{Expression, Function};
+use crate::printer::pr_str;
 
-use self::error::EvalError;
-pub use self::scope::{Scope, ScopeError};
+pub use self::builtins::{print_options, register_builtins, BuiltinError};
+pub use self::error::EvalError;
+pub use self::lambda::Lambda;
+pub use self::scope::Scope;
+use self::special_forms::eval_special_form;
 
+mod builtins;
 mod error;
+mod lambda;
+pub mod numeric;
 mod scope;
+mod special_forms;
 
 pub fn eval(scope: &mut Scope, expr: &Expression) -> Result<Expression, EvalError> {
     match expr {
         Expression::Identifier(ident) => Ok(scope.get(ident)?),
-        Expression::List(data) => eval_list(scope, &data),
+        Expression::List(data) => eval_list(scope, data),
+        Expression::Vector(data) => Ok(Expression::Vector(eval_all(scope, data)?)),
+        Expression::Map(map) => Ok(Expression::Map(
+            map.iter()
+                .map(|(k, v)| Ok((eval(scope, k)?, eval(scope, v)?)))
+                .collect::<Result<_, EvalError>>()?,
+        )),
+        Expression::WithMeta(form, meta) => match form.as_ref() {
+            // collection literals keep their metadata, code does not
+            Expression::Vector(_) | Expression::Map(_) | Expression::Set(_) => {
+                Ok(eval(scope, form)?.with_meta(meta.clone()))
+            }
+            _ => eval(scope, form),
+        },
+        Expression::Set(set) => Ok(Expression::Set(
+            set.iter()
+                .map(|element| eval(scope, element))
+                .collect::<Result<_, EvalError>>()?,
+        )),
         c => Ok(c.clone()),
     }
 }
 
+/// Whether `expr` counts as true in conditions: everything except `false` and
+/// `nil` does.
+pub fn is_truthy(expr: &Expression) -> bool {
+    !matches!(expr, Expression::Bool(false) | Expression::Nil)
+}
+
 fn eval_list(scope: &mut Scope, list: &[Expression]) -> Result<Expression, EvalError> {
-    if list.len() == 0 {
-        return Err(EvalError::EmptyList)
+    if list.is_empty() {
+        return Err(EvalError::EmptyList);
+    }
+    if let Expression::Identifier(name) = list[0].without_meta() {
+        if let Some(result) = eval_special_form(scope, name, &list[1..]) {
+            return result;
+        }
     }
 
     let func = eval(scope, &list[0])?;
     match func {
         Expression::Fn(func) => {
-            let args = list[1..]
-                .iter()
-                .map(|expr| eval(scope, expr))
-                .collect::<Result<Vec<_>, _>>()?;
-            Ok(func.call(&args)?)
+            let args = eval_all(scope, &list[1..])?;
+            match func {
+                Function::Regular(lambda) => lambda.call(&args),
+                func => Ok(func.call(&args)?),
+            }
         }
-        expr => Err(EvalError::NotAFunction(expr)),
+        expr => Err(EvalError::NotAFunction(pr_str(&expr))),
     }
 }
 
+fn eval_all(scope: &mut Scope, exprs: &[Expression]) -> Result<Vec<Expression>, EvalError> {
+    exprs.iter().map(|expr| eval(scope, expr)).collect()
+}
+
+/// Helpers shared by the tests of the evaluator.
 #[cfg(test)]
+mod testing {
+    use super::{eval, register_builtins, Scope};
+    use crate::reader::{Expression, Reader};
+    use failure::Error;
+
+    /// Evaluates all forms of `code` in a fresh scope with the builtins, returns the
+    /// value of the last one.
+    pub(super) fn eval_all(code: &str) -> Result<Expression, Error> {
+        let mut scope = Scope::new();
+        register_builtins(&mut scope);
+        let mut result = Expression::Nil;
+        for expr in Reader::from_string(code) {
+            result = eval(&mut scope, &expr?)?;
+        }
+        Ok(result)
+    }
+
+    pub(super) fn read(code: &str) -> Expression {
+        Reader::from_string(code).read().unwrap()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::approx_constant)]
 mod test {
     use super::*;
 
@@ -60,6 +126,28 @@ mod test {
             Ok(())
         }
 
+        #[test]
+        fn should_eval_keywords_booleans_and_nil_to_themselves() -> Result<(), Error> {
+            // given
+            let mut scope = Scope::new();
+            let expr = Reader::from_string("[:key true false nil]").read()?;
+
+            // expect
+            assert_eq!(expr, eval(&mut scope, &expr)?);
+            Ok(())
+        }
+
+        #[test]
+        fn should_treat_only_false_and_nil_as_falsy() {
+            // expect
+            assert!(!is_truthy(&Expr::Bool(false)));
+            assert!(!is_truthy(&Expr::Nil));
+            assert!(is_truthy(&Expr::Bool(true)));
+            assert!(is_truthy(&Expr::Integer(0)));
+            assert!(is_truthy(&Expr::String(String::new())));
+            assert!(is_truthy(&Expr::List(vec![])));
+        }
+
         mod identifiers {
             use super::*;
             use crate::eval::scope::ScopeError::IdentifierNotFound;
@@ -148,6 +236,25 @@ mod test {
                 Ok(())
             }
 
+            #[test]
+            fn should_eval_collection_elements() -> Result<(), Error> {
+                // given
+                let native_func: fn(&[Expression]) -> Result<Expression, Error> =
+                    |exprs| Ok(exprs.first().unwrap().clone());
+                let mut scope = Scope::new();
+                scope.put(&"identity", Expr::Fn(Function::Native(native_func)));
+                scope.put(&"x", Expr::Integer(1));
+                let expr = Reader::from_string("[x (identity 2) {x [x]} #{x 1}]").read()?;
+
+                // when
+                let result = eval(&mut scope, &expr)?;
+
+                // then
+                let expected = Reader::from_string("[1 2 {1 [1]} #{1}]").read()?;
+                assert_eq!(expected, result);
+                Ok(())
+            }
+
             #[test]
             fn should_return_error_when_evaluating_empty_list() -> Result<(), Error> {
                 // given
@@ -0,0 +1,400 @@
+use crate::number::decimal::{Decimal, DecimalError, RoundingMode};
+use crate::number::{normalize_bigint, normalize_ratio};
+use crate::reader::Expression;
+use failure::Error;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{ToPrimitive, Zero};
+
+#[derive(Debug, Fail)]
+pub enum NumericError {
+    #[fail(display = "Expected a number but got {}", _0)]
+    NotANumber(&'static str),
+
+    #[fail(display = "Expected {} but got {}", _0, _1)]
+    UnexpectedType(&'static str, &'static str),
+
+    #[fail(display = "Division by zero")]
+    DivisionByZero,
+
+    #[fail(display = "Cannot convert {} to an exact number", _0)]
+    NotFinite(f64),
+
+    #[fail(
+        display = "Cannot mix decimal with {}, convert it explicitly with decimal, float or rationalize",
+        _0
+    )]
+    MixedDecimal(&'static str),
+
+    #[fail(display = "Scale {} is larger than the maximum of {}", _0, _1)]
+    ScaleTooLarge(i64, u32),
+}
+
+/// Operands of a binary operation converted to a common representation.
+///
+/// Operands are promoted to the wider of the two types, in order: integer, big
+/// integer, ratio, float. Exact numbers mixed with a float become floats.
+/// Decimals only combine with other decimals.
+enum Operands {
+    Integers(i64, i64),
+    BigIntegers(BigInt, BigInt),
+    Ratios(BigRational, BigRational),
+    Floats(f64, f64),
+    Decimals(Decimal, Decimal),
+}
+
+fn coerce(a: &Expression, b: &Expression) -> Result<Operands, Error> {
+    use Expression::*;
+    Ok(match (a, b) {
+        (Integer(a), Integer(b)) => Operands::Integers(*a, *b),
+        (Decimal(a), Decimal(b)) => Operands::Decimals(a.clone(), b.clone()),
+        (Decimal(_), other) | (other, Decimal(_)) => {
+            ensure_number(other)?;
+            return Err(NumericError::MixedDecimal(other.type_name()).into());
+        }
+        (Float(_), _) | (_, Float(_)) => Operands::Floats(to_f64(a)?, to_f64(b)?),
+        (Ratio(_), _) | (_, Ratio(_)) => Operands::Ratios(to_ratio(a)?, to_ratio(b)?),
+        _ => Operands::BigIntegers(to_bigint(a)?, to_bigint(b)?),
+    })
+}
+
+fn to_f64(expr: &Expression) -> Result<f64, Error> {
+    match expr {
+        Expression::Integer(value) => Ok(*value as f64),
+        Expression::BigInt(value) => Ok(value.to_f64().unwrap_or(f64::NAN)),
+        Expression::Ratio(value) => Ok(value.to_f64().unwrap_or(f64::NAN)),
+        Expression::Float(value) => Ok(*value),
+        other => Err(NumericError::NotANumber(other.type_name()).into()),
+    }
+}
+
+fn to_ratio(expr: &Expression) -> Result<BigRational, Error> {
+    match expr {
+        Expression::Ratio(value) => Ok(value.clone()),
+        other => Ok(BigRational::from_integer(to_bigint(other)?)),
+    }
+}
+
+fn to_bigint(expr: &Expression) -> Result<BigInt, Error> {
+    match expr {
+        Expression::Integer(value) => Ok(BigInt::from(*value)),
+        Expression::BigInt(value) => Ok(value.clone()),
+        other => Err(NumericError::NotANumber(other.type_name()).into()),
+    }
+}
+
+pub fn add(a: &Expression, b: &Expression) -> Result<Expression, Error> {
+    Ok(match coerce(a, b)? {
+        Operands::Integers(a, b) => match a.checked_add(b) {
+            Some(sum) => Expression::Integer(sum),
+            None => Expression::BigInt(BigInt::from(a) + b),
+        },
+        Operands::BigIntegers(a, b) => normalize_bigint(a + b),
+        Operands::Ratios(a, b) => normalize_ratio(a + b),
+        Operands::Floats(a, b) => Expression::Float(a + b),
+        Operands::Decimals(a, b) => Expression::Decimal(a.add(&b)),
+    })
+}
+
+pub fn subtract(a: &Expression, b: &Expression) -> Result<Expression, Error> {
+    Ok(match coerce(a, b)? {
+        Operands::Integers(a, b) => match a.checked_sub(b) {
+            Some(difference) => Expression::Integer(difference),
+            None => Expression::BigInt(BigInt::from(a) - b),
+        },
+        Operands::BigIntegers(a, b) => normalize_bigint(a - b),
+        Operands::Ratios(a, b) => normalize_ratio(a - b),
+        Operands::Floats(a, b) => Expression::Float(a - b),
+        Operands::Decimals(a, b) => Expression::Decimal(a.sub(&b)),
+    })
+}
+
+pub fn multiply(a: &Expression, b: &Expression) -> Result<Expression, Error> {
+    Ok(match coerce(a, b)? {
+        Operands::Integers(a, b) => match a.checked_mul(b) {
+            Some(product) => Expression::Integer(product),
+            None => Expression::BigInt(BigInt::from(a) * b),
+        },
+        Operands::BigIntegers(a, b) => normalize_bigint(a * b),
+        Operands::Ratios(a, b) => normalize_ratio(a * b),
+        Operands::Floats(a, b) => Expression::Float(a * b),
+        Operands::Decimals(a, b) => Expression::Decimal(a.mul(&b)),
+    })
+}
+
+/// Divides numbers. Division of exact numbers is exact and yields a ratio unless
+/// the result is a whole number. Decimal division fails when the quotient has no
+/// finite decimal expansion, use `divide_decimals` to round it instead.
+pub fn divide(a: &Expression, b: &Expression) -> Result<Expression, Error> {
+    let (a, b) = match coerce(a, b)? {
+        Operands::Floats(a, b) => return Ok(Expression::Float(a / b)),
+        Operands::Decimals(a, b) => return Ok(Expression::Decimal(a.div(&b)?)),
+        Operands::Integers(a, b) => (BigRational::from_integer(a.into()), BigInt::from(b).into()),
+        Operands::BigIntegers(a, b) => (BigRational::from_integer(a), BigRational::from_integer(b)),
+        Operands::Ratios(a, b) => (a, b),
+    };
+    if b.is_zero() {
+        return Err(NumericError::DivisionByZero.into());
+    }
+    Ok(normalize_ratio(a / b))
+}
+
+pub fn divide_decimals(
+    a: &Expression,
+    b: &Expression,
+    scale: u32,
+    mode: RoundingMode,
+) -> Result<Expression, Error> {
+    match (a, b) {
+        (Expression::Decimal(a), Expression::Decimal(b)) => {
+            Ok(Expression::Decimal(a.div_rounded(b, scale, mode)?))
+        }
+        (Expression::Decimal(_), other) | (other, _) => {
+            Err(NumericError::UnexpectedType("a decimal", other.type_name()).into())
+        }
+    }
+}
+
+pub fn negate(a: &Expression) -> Result<Expression, Error> {
+    match a {
+        Expression::Decimal(value) => Ok(Expression::Decimal(value.neg())),
+        _ => subtract(&Expression::Integer(0), a),
+    }
+}
+
+pub fn reciprocal(a: &Expression) -> Result<Expression, Error> {
+    match a {
+        Expression::Decimal(value) => Ok(Expression::Decimal(
+            Decimal::from_integer(1.into()).div(value)?,
+        )),
+        _ => divide(&Expression::Integer(1), a),
+    }
+}
+
+/// Checks that the expression is a number, so that operations given a single
+/// argument do not silently pass anything through.
+pub fn ensure_number(a: &Expression) -> Result<(), Error> {
+    match a {
+        Expression::Integer(_)
+        | Expression::BigInt(_)
+        | Expression::Ratio(_)
+        | Expression::Float(_)
+        | Expression::Decimal(_) => Ok(()),
+        other => Err(NumericError::NotANumber(other.type_name()).into()),
+    }
+}
+
+pub fn numerator(a: &Expression) -> Result<Expression, Error> {
+    match a {
+        Expression::Ratio(value) => Ok(normalize_bigint(value.numer().clone())),
+        Expression::Integer(_) | Expression::BigInt(_) => Ok(a.clone()),
+        other => Err(NumericError::UnexpectedType("an exact number", other.type_name()).into()),
+    }
+}
+
+pub fn denominator(a: &Expression) -> Result<Expression, Error> {
+    match a {
+        Expression::Ratio(value) => Ok(normalize_bigint(value.denom().clone())),
+        Expression::Integer(_) | Expression::BigInt(_) => Ok(Expression::Integer(1)),
+        other => Err(NumericError::UnexpectedType("an exact number", other.type_name()).into()),
+    }
+}
+
+/// Converts number to a decimal. Without a scale the conversion must be exact;
+/// floats are taken at their shortest printed representation.
+pub fn to_decimal(
+    a: &Expression,
+    rounding: Option<(u32, RoundingMode)>,
+) -> Result<Expression, Error> {
+    Ok(Expression::Decimal(match (a, rounding) {
+        (Expression::Decimal(value), None) => value.clone(),
+        (_, Some((scale, mode))) => Decimal::from_ratio(&exact_ratio(a)?, scale, mode),
+        (_, None) => {
+            Decimal::from_ratio_exact(&exact_ratio(a)?).ok_or(DecimalError::NonTerminating)?
+        }
+    }))
+}
+
+/// Converts any number to a float.
+pub fn to_float(a: &Expression) -> Result<Expression, Error> {
+    match a {
+        Expression::Decimal(value) => Ok(Expression::Float(value.to_f64())),
+        other => Ok(Expression::Float(to_f64(other)?)),
+    }
+}
+
+/// Converts any number to an integer, truncating towards zero.
+pub fn to_integer(a: &Expression) -> Result<Expression, Error> {
+    Ok(normalize_bigint(exact_ratio(a)?.trunc().to_integer()))
+}
+
+/// Converts number to an exact one. Floats become the simplest ratio that prints
+/// the same, so `0.1` becomes `1/10` rather than its binary approximation.
+pub fn rationalize(a: &Expression) -> Result<Expression, Error> {
+    Ok(normalize_ratio(exact_ratio(a)?))
+}
+
+fn exact_ratio(a: &Expression) -> Result<BigRational, Error> {
+    match a {
+        Expression::Float(value) if !value.is_finite() => {
+            Err(NumericError::NotFinite(*value).into())
+        }
+        Expression::Float(value) => Ok(value.to_string().parse::<Decimal>()?.to_ratio()),
+        Expression::Decimal(value) => Ok(value.to_ratio()),
+        other => to_ratio(other),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reader::Expression::{Float, Integer, String};
+
+    fn ratio(numerator: i64, denominator: i64) -> Expression {
+        normalize_ratio(BigRational::new(numerator.into(), denominator.into()))
+    }
+
+    #[test]
+    fn should_promote_to_big_integer_on_overflow() -> Result<(), Error> {
+        // when
+        let sum = add(&Integer(i64::MAX), &Integer(1))?;
+        let difference = subtract(&Integer(i64::MIN), &Integer(1))?;
+        let product = multiply(&Integer(i64::MAX), &Integer(2))?;
+
+        // then
+        assert_eq!(Expression::BigInt(BigInt::from(i64::MAX) + 1), sum);
+        assert_eq!(Expression::BigInt(BigInt::from(i64::MIN) - 1), difference);
+        assert_eq!(Expression::BigInt(BigInt::from(i64::MAX) * 2), product);
+        Ok(())
+    }
+
+    #[test]
+    fn should_demote_big_integer_when_result_fits() -> Result<(), Error> {
+        // given
+        let big = add(&Integer(i64::MAX), &Integer(1))?;
+
+        // when
+        let result = subtract(&big, &Integer(1))?;
+
+        // then
+        assert_eq!(Integer(i64::MAX), result);
+        Ok(())
+    }
+
+    #[test]
+    fn should_convert_integers_to_floats_when_mixed() -> Result<(), Error> {
+        // expect
+        assert_eq!(Float(3.5), add(&Integer(1), &Float(2.5))?);
+        assert_eq!(Float(5.0), multiply(&Float(2.5), &Integer(2))?);
+        assert_eq!(
+            Float(2f64.powi(64)),
+            multiply(&Expression::BigInt(BigInt::from(2).pow(63u32)), &Float(2.0))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn should_divide_exactly() -> Result<(), Error> {
+        // expect
+        assert_eq!(ratio(1, 3), divide(&Integer(1), &Integer(3))?);
+        assert_eq!(Integer(2), divide(&Integer(6), &Integer(3))?);
+        assert_eq!(Integer(1), add(&ratio(1, 3), &ratio(2, 3))?);
+        assert_eq!(ratio(5, 6), add(&ratio(1, 2), &ratio(1, 3))?);
+        assert_eq!(ratio(3, 2), multiply(&ratio(1, 2), &Integer(3))?);
+        assert_eq!(Float(0.75), add(&ratio(1, 4), &Float(0.5))?);
+        Ok(())
+    }
+
+    #[test]
+    fn should_fail_on_exact_division_by_zero() {
+        // when
+        let error = divide(&Integer(1), &Integer(0)).err().unwrap();
+
+        // then
+        match error.downcast::<NumericError>() {
+            Ok(NumericError::DivisionByZero) => (),
+            other => panic!("Expected division by zero error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_give_numerator_and_denominator() -> Result<(), Error> {
+        // expect
+        assert_eq!(Integer(-2), numerator(&ratio(-4, 6))?);
+        assert_eq!(Integer(3), denominator(&ratio(-4, 6))?);
+        assert_eq!(Integer(1), denominator(&Integer(7))?);
+        assert!(numerator(&Float(0.5)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn should_rationalize_floats() -> Result<(), Error> {
+        // expect
+        assert_eq!(ratio(1, 10), rationalize(&Float(0.1))?);
+        assert_eq!(ratio(-5, 4), rationalize(&Float(-1.25))?);
+        assert_eq!(Integer(3), rationalize(&Float(3.0))?);
+        assert_eq!(ratio(2, 3), rationalize(&ratio(2, 3))?);
+        assert!(rationalize(&Float(f64::INFINITY)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn should_not_mix_decimals_with_other_numbers() -> Result<(), Error> {
+        // given
+        let price = Expression::Decimal("12.50".parse()?);
+
+        // expect
+        assert_eq!(Expression::Decimal("25.00".parse()?), add(&price, &price)?);
+        for other in &[Integer(1), ratio(1, 2), Float(0.5)] {
+            match add(&price, other).err().unwrap().downcast::<NumericError>() {
+                Ok(NumericError::MixedDecimal(_)) => (),
+                error => panic!("Expected mixed decimal error, got {:?}", error),
+            }
+        }
+        match add(&price, &String("1".to_owned()))
+            .err()
+            .unwrap()
+            .downcast::<NumericError>()
+        {
+            Ok(NumericError::NotANumber("string")) => (),
+            error => panic!("Expected not a number error, got {:?}", error),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn should_convert_to_and_from_decimals_explicitly() -> Result<(), Error> {
+        // expect
+        assert_eq!(
+            Expression::Decimal("0.1".parse()?),
+            to_decimal(&Float(0.1), None)?
+        );
+        assert_eq!(
+            Expression::Decimal("0.25".parse()?),
+            to_decimal(&ratio(1, 4), None)?
+        );
+        assert!(to_decimal(&ratio(1, 3), None).is_err());
+        assert_eq!(
+            Expression::Decimal("0.33".parse()?),
+            to_decimal(&ratio(1, 3), Some((2, RoundingMode::HalfEven)))?
+        );
+        let decimal = Expression::Decimal("-2.75".parse()?);
+        assert_eq!(ratio(-11, 4), rationalize(&decimal)?);
+        assert_eq!(Float(-2.75), to_float(&decimal)?);
+        assert_eq!(Integer(-2), to_integer(&decimal)?);
+        assert_eq!(Integer(3), to_integer(&Float(3.9))?);
+        Ok(())
+    }
+
+    #[test]
+    fn should_fail_on_non_numbers() {
+        // when
+        let error = add(&Integer(1), &String("1".to_owned())).err().unwrap();
+
+        // then
+        match error.downcast::<NumericError>() {
+            Ok(NumericError::NotANumber("string")) => (),
+            other => panic!("Expected not a number error, got {:?}", other),
+        }
+    }
+}
@@ -1,26 +1,89 @@
 use crate::reader::Expression;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::{Rc, Weak};
 
+/// Names visible to evaluated code.
+///
+/// A scope is a handle to a shared frame of names, so clones of a scope see the
+/// same names. Scopes made with `child` see the names of their parents unless
+/// they shadow them.
+///
+/// Functions defined globally are stored in the global scope and lead back to it
+/// through the scopes they capture. So that such a cycle does not keep the global
+/// scope alive, nested scopes refer to it weakly and stop seeing its names once
+/// it is dropped.
+#[derive(Clone, Default)]
 pub struct Scope {
+    frame: Rc<RefCell<Frame>>,
+}
+
+#[derive(Default)]
+struct Frame {
     names: HashMap<String, Expression>,
+    parent: Option<Parent>,
+}
+
+enum Parent {
+    Local(Scope),
+    Global(Weak<RefCell<Frame>>),
+}
+
+impl Parent {
+    fn scope(&self) -> Option<Scope> {
+        match self {
+            Parent::Local(scope) => Some(scope.clone()),
+            Parent::Global(frame) => frame.upgrade().map(|frame| Scope { frame }),
+        }
+    }
 }
 
 impl Scope {
     pub fn new() -> Self {
-        Self {
+        Self::default()
+    }
+
+    /// Empty scope nested in this one.
+    pub fn child(&self) -> Self {
+        let parent = if self.frame.borrow().parent.is_none() {
+            Parent::Global(Rc::downgrade(&self.frame))
+        } else {
+            Parent::Local(self.clone())
+        };
+        let frame = Frame {
             names: HashMap::new(),
+            parent: Some(parent),
+        };
+        Self {
+            frame: Rc::new(RefCell::new(frame)),
+        }
+    }
+
+    /// Outermost scope this one is nested in, or this one if it is not nested.
+    pub fn global(&self) -> Self {
+        match self.frame.borrow().parent.as_ref().and_then(Parent::scope) {
+            Some(parent) => parent.global(),
+            None => self.clone(),
         }
     }
 
-    pub fn put(&mut self, name: &ToString, value: Expression) {
-        self.names.insert(name.to_string(), value);
+    pub fn put(&mut self, name: &dyn ToString, value: Expression) {
+        self.frame
+            .borrow_mut()
+            .names
+            .insert(name.to_string(), value);
     }
 
     pub fn get(&self, name: &str) -> Result<Expression, ScopeError> {
-        self.names
-            .get(name)
-            .ok_or_else(|| ScopeError::IdentifierNotFound(name.to_string()))
-            .map(Clone::clone)
+        let frame = self.frame.borrow();
+        match (
+            frame.names.get(name),
+            frame.parent.as_ref().and_then(Parent::scope),
+        ) {
+            (Some(value), _) => Ok(value.clone()),
+            (None, Some(parent)) => parent.get(name),
+            (None, None) => Err(ScopeError::IdentifierNotFound(name.to_string())),
+        }
     }
 }
 
@@ -29,3 +92,29 @@ pub enum ScopeError {
     #[fail(display = "Identifier not found in scope: {}", _0)]
     IdentifierNotFound(String),
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_shadow_names_of_parent_scopes() -> Result<(), ScopeError> {
+        // given
+        let mut global = Scope::new();
+        global.put(&"a", Expression::Integer(1));
+        global.put(&"b", Expression::Integer(2));
+        let mut child = global.child();
+
+        // when
+        child.put(&"a", Expression::Integer(3));
+        child.global().put(&"c", Expression::Integer(4));
+
+        // then
+        assert_eq!(Expression::Integer(3), child.get("a")?);
+        assert_eq!(Expression::Integer(2), child.get("b")?);
+        assert_eq!(Expression::Integer(1), global.get("a")?);
+        assert_eq!(Expression::Integer(4), global.get("c")?);
+        assert!(global.child().get("d").is_err());
+        Ok(())
+    }
+}
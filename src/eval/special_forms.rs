@@ -0,0 +1,247 @@
+use super::error::EvalError::{self, InvalidForm, WrongArity};
+use super::{eval, is_truthy, Lambda, Scope};
+use crate::reader::{Expression, Function};
+use std::rc::Rc;
+
+/// Evaluates `(name args...)` if `name` is a special form, `None` otherwise.
+///
+/// Special forms get their arguments unevaluated and decide what to evaluate
+/// themselves, so they cannot be functions.
+pub fn eval_special_form(
+    scope: &mut Scope,
+    name: &str,
+    args: &[Expression],
+) -> Option<Result<Expression, EvalError>> {
+    Some(match name {
+        "def" => def(scope, args),
+        "if" => eval_if(scope, args),
+        "do" => eval_do(scope, args),
+        "let" => eval_let(scope, args),
+        "quote" => quote(args),
+        "fn" => eval_fn(scope, "fn", args),
+        "lambda" => eval_fn(scope, "lambda", args),
+        "defn" => defn(scope, args),
+        _ => return None,
+    })
+}
+
+/// `(def name value)`, binds the value in the global scope and returns it.
+fn def(scope: &mut Scope, args: &[Expression]) -> Result<Expression, EvalError> {
+    let (name, value) = match args {
+        [name, value] => (symbol("def", name)?, value),
+        _ => return Err(WrongArity("def".into(), "2".into(), args.len())),
+    };
+    let value = eval(scope, value)?;
+    scope.global().put(&name, value.clone());
+    Ok(value)
+}
+
+/// `(if condition then else?)`, a missing else branch gives nil.
+fn eval_if(scope: &mut Scope, args: &[Expression]) -> Result<Expression, EvalError> {
+    let (condition, then, otherwise) = match args {
+        [condition, then] => (condition, then, None),
+        [condition, then, otherwise] => (condition, then, Some(otherwise)),
+        _ => return Err(WrongArity("if".into(), "2 or 3".into(), args.len())),
+    };
+    if is_truthy(&eval(scope, condition)?) {
+        eval(scope, then)
+    } else {
+        otherwise.map_or(Ok(Expression::Nil), |otherwise| eval(scope, otherwise))
+    }
+}
+
+/// `(do forms...)`, evaluates forms in order and returns the value of the last one.
+pub(super) fn eval_do(scope: &mut Scope, body: &[Expression]) -> Result<Expression, EvalError> {
+    let mut result = Expression::Nil;
+    for form in body {
+        result = eval(scope, form)?;
+    }
+    Ok(result)
+}
+
+/// `(let [name value ...] body...)`, evaluates the body with the names bound in a
+/// new scope. Each value sees the names bound before it.
+///
+/// Every name gets a scope of its own, so a function bound here captures only the
+/// names before it and not the scope that holds the function itself.
+fn eval_let(scope: &mut Scope, args: &[Expression]) -> Result<Expression, EvalError> {
+    let (bindings, body) = match args {
+        [bindings, body @ ..] => match bindings.without_meta() {
+            Expression::Vector(bindings) => (bindings, body),
+            other => {
+                let message = format!("bindings must be a vector, got {}", other.type_name());
+                return Err(InvalidForm("let", message));
+            }
+        },
+        [] => return Err(WrongArity("let".into(), "at least 1".into(), 0)),
+    };
+    if bindings.len() % 2 != 0 {
+        let message = "bindings must come in name and value pairs".to_owned();
+        return Err(InvalidForm("let", message));
+    }
+    let mut inner = scope.child();
+    for pair in bindings.chunks(2) {
+        let name = symbol("let", &pair[0])?;
+        let value = eval(&mut inner, &pair[1])?;
+        inner = inner.child();
+        inner.put(&name, value);
+    }
+    eval_do(&mut inner, body)
+}
+
+/// `(quote form)`, returns the form unevaluated.
+fn quote(args: &[Expression]) -> Result<Expression, EvalError> {
+    match args {
+        [form] => Ok(form.clone()),
+        _ => Err(WrongArity("quote".into(), "1".into(), args.len())),
+    }
+}
+
+/// `(fn name? [params...] body...)`, creates a function that closes over the
+/// current scope. A name lets the function call itself.
+fn eval_fn(
+    scope: &Scope,
+    form: &'static str,
+    args: &[Expression],
+) -> Result<Expression, EvalError> {
+    let (name, rest) = match args.first().map(Expression::without_meta) {
+        Some(Expression::Identifier(name)) => (Some(name.clone()), &args[1..]),
+        _ => (None, args),
+    };
+    match rest {
+        [params, body @ ..] => lambda(Lambda::new(form, name, params, body, scope)?),
+        [] => Err(WrongArity(form.into(), "at least 1".into(), args.len())),
+    }
+}
+
+/// `(defn name [params...] body...)`, same as `(def name (fn name [params...] body...))`.
+fn defn(scope: &mut Scope, args: &[Expression]) -> Result<Expression, EvalError> {
+    let (name, params, body) = match args {
+        [name, params, body @ ..] => (symbol("defn", name)?, params, body),
+        _ => return Err(WrongArity("defn".into(), "at least 2".into(), args.len())),
+    };
+    let value = lambda(Lambda::new(
+        "defn",
+        Some(name.to_owned()),
+        params,
+        body,
+        scope,
+    )?)?;
+    scope.global().put(&name, value.clone());
+    Ok(value)
+}
+
+fn lambda(lambda: Lambda) -> Result<Expression, EvalError> {
+    Ok(Expression::Fn(Function::Regular(Rc::new(lambda))))
+}
+
+/// Name of the symbol bound by special form `form`.
+fn symbol<'a>(form: &'static str, expr: &'a Expression) -> Result<&'a str, EvalError> {
+    match expr.without_meta() {
+        Expression::Identifier(name) => Ok(name),
+        other => {
+            let message = format!("expected a symbol to bind, got {}", other.type_name());
+            Err(InvalidForm(form, message))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::eval::testing::{eval_all, read};
+    use failure::Error;
+
+    #[test]
+    fn should_define_global_names() -> Result<(), Error> {
+        // expect
+        assert_eq!(
+            Expression::Integer(3),
+            eval_all("(def x 1) (def y (+ x 2)) y")?
+        );
+        assert_eq!(Expression::Integer(5), eval_all("(def ^:private x 5) x")?);
+        assert_eq!(
+            Expression::Integer(2),
+            eval_all("(let [a 1] (def b (+ a 1))) b")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn should_evaluate_only_chosen_branch() -> Result<(), Error> {
+        // expect
+        assert_eq!(Expression::Integer(1), eval_all("(if true 1 undefined)")?);
+        assert_eq!(Expression::Integer(2), eval_all("(if nil undefined 2)")?);
+        assert_eq!(Expression::Nil, eval_all("(if false undefined)")?);
+        assert_eq!(Expression::Integer(1), eval_all("(if 0 1 2)")?);
+        Ok(())
+    }
+
+    #[test]
+    fn should_evaluate_forms_in_sequence() -> Result<(), Error> {
+        // expect
+        assert_eq!(Expression::Integer(2), eval_all("(do (def a 1) (+ a 1))")?);
+        assert_eq!(Expression::Nil, eval_all("(do)")?);
+        Ok(())
+    }
+
+    #[test]
+    fn should_bind_names_locally() -> Result<(), Error> {
+        // expect
+        assert_eq!(
+            Expression::Integer(3),
+            eval_all("(let [a 1 b (+ a 1)] (+ a b))")?
+        );
+        assert_eq!(
+            Expression::Integer(1),
+            eval_all("(def a 1) (let [a 2] a) a")?
+        );
+        assert_eq!(Expression::Nil, eval_all("(let [])")?);
+        assert!(eval_all("(let [a 1] a) a").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn should_return_quoted_forms_unevaluated() -> Result<(), Error> {
+        // expect
+        assert_eq!(read("(+ 1 2)"), eval_all("(quote (+ 1 2))")?);
+        assert_eq!(read("[a b]"), eval_all("'[a b]")?);
+        assert_eq!(read("x"), eval_all("'x")?);
+        Ok(())
+    }
+
+    #[test]
+    fn should_report_malformed_special_forms() {
+        // given
+        let cases = [
+            ("(def x)", "def expects 2 argument(s) but got 1"),
+            (
+                "(def 1 2)",
+                "Invalid def form: expected a symbol to bind, got integer",
+            ),
+            ("(if)", "if expects 2 or 3 argument(s) but got 0"),
+            ("(let)", "let expects at least 1 argument(s) but got 0"),
+            (
+                "(let (a 1) a)",
+                "Invalid let form: bindings must be a vector, got list",
+            ),
+            (
+                "(let [a] a)",
+                "Invalid let form: bindings must come in name and value pairs",
+            ),
+            (
+                "(let [:a 1] a)",
+                "Invalid let form: expected a symbol to bind, got keyword",
+            ),
+            ("(quote a b)", "quote expects 1 argument(s) but got 2"),
+        ];
+
+        for (code, message) in &cases {
+            // when
+            let error = eval_all(code).err().unwrap();
+
+            // then
+            assert_eq!(*message, error.to_string(), "{}", code);
+        }
+    }
+}
@@ -0,0 +1,406 @@
+use crate::cst::{Cst, Node, NodeKind};
+use crate::printer::{pr_str, BODY_FORMS};
+use crate::reader::{Expression, Reader};
+use crate::tokenizer::{SliceToken, SliceTokenizer};
+use failure::Error;
+use std::collections::HashMap;
+
+#[derive(Debug, Fail)]
+pub enum FormatError {
+    #[fail(
+        display = "Indentation rules map head symbols to numbers of arguments or nil, got {}",
+        _0
+    )]
+    InvalidRules(String),
+}
+
+/// Indentation rules of the formatter.
+///
+/// Lists whose head symbol has a rule are indented like bodies: their first
+/// arguments, as many as the rule says, are indented by four spaces when they
+/// start a line, the remaining ones by two. Arguments of other lists are aligned
+/// with the first one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatConfig {
+    rules: HashMap<String, usize>,
+}
+
+impl Default for FormatConfig {
+    fn default() -> Self {
+        Self {
+            rules: BODY_FORMS
+                .iter()
+                .map(|(head, args)| (head.to_string(), *args))
+                .collect(),
+        }
+    }
+}
+
+impl FormatConfig {
+    pub fn with_rule(mut self, head: &str, args: usize) -> Self {
+        self.rules.insert(head.to_owned(), args);
+        self
+    }
+
+    pub fn without_rule(mut self, head: &str) -> Self {
+        self.rules.remove(head);
+        self
+    }
+
+    /// Default rules updated by a map like `{my-macro 1, if nil}` read from `code`.
+    /// A number sets the rule of a head, `nil` removes it.
+    pub fn read(code: &str) -> Result<Self, Error> {
+        let rules = match Reader::from_string(code).read()? {
+            Expression::Map(map) => map,
+            other => return Err(FormatError::InvalidRules(pr_str(&other)).into()),
+        };
+        let mut config = Self::default();
+        for (head, value) in rules.iter() {
+            config = match (head, value) {
+                (Expression::Identifier(head), Expression::Integer(args)) if *args >= 0 => {
+                    config.with_rule(head, *args as usize)
+                }
+                (Expression::Identifier(head), Expression::Nil) => config.without_rule(head),
+                _ => {
+                    let rule = format!("{} {}", pr_str(head), pr_str(value));
+                    return Err(FormatError::InvalidRules(rule).into());
+                }
+            };
+        }
+        Ok(config)
+    }
+}
+
+/// Formats `source`, keeping its comments and line breaks.
+///
+/// Lines are indented according to `config`, forms on the same line are separated
+/// by a single space, there is no space just inside delimiters and at most one
+/// blank line in a row. Formatting formatted code changes nothing.
+pub fn format(source: &str, config: &FormatConfig) -> Result<String, Error> {
+    let cst = Cst::parse(source)?;
+    let mut formatter = Formatter {
+        cst: &cst,
+        config,
+        output: String::new(),
+        line: 0,
+        column: 0,
+    };
+    formatter.container(cst.root());
+    if !formatter.output.is_empty() {
+        formatter.output.push('\n');
+    }
+    Ok(formatter.output)
+}
+
+struct Formatter<'a> {
+    cst: &'a Cst<'a>,
+    config: &'a FormatConfig,
+    output: String,
+    line: usize,
+    column: usize,
+}
+
+/// Where lines inside a node are indented to.
+struct Indentation {
+    kind: NodeKind,
+    /// Column of the first character of the node.
+    start: usize,
+    /// Length of the opening delimiter or reader macro.
+    open: usize,
+    /// Whether the node is a list with a symbol for its head.
+    symbol_head: bool,
+    /// Rule of the head of the list.
+    rule: Option<usize>,
+    /// Column of the first argument of a list, if it is on the line of the head.
+    first_arg: Option<usize>,
+}
+
+impl Indentation {
+    /// Indentation of a line starting with element number `element` of the node,
+    /// the head of a list being the element 0.
+    fn of(&self, element: usize) -> usize {
+        match self.kind {
+            NodeKind::Root => 0,
+            NodeKind::List => match self.rule {
+                Some(args) if element >= 1 && element <= args => self.start + 4,
+                Some(_) if element > 0 => self.start + 2,
+                None if self.symbol_head => self.first_arg.unwrap_or(self.start + 1),
+                _ => self.start + self.open,
+            },
+            NodeKind::Vector | NodeKind::Map | NodeKind::Set => self.start + self.open,
+            _ => self.start,
+        }
+    }
+}
+
+impl Formatter<'_> {
+    fn push(&mut self, text: &str) {
+        self.output.push_str(text);
+        match text.rfind('\n') {
+            Some(newline) => {
+                self.line += text.matches('\n').count();
+                self.column = text[newline + 1..].chars().count();
+            }
+            None => self.column += text.chars().count(),
+        }
+    }
+
+    fn node(&mut self, node: &Node) {
+        match node.kind {
+            NodeKind::Comment => {
+                let text = self.cst.text(node);
+                // line comments lose trailing whitespace, block comments are kept as they are
+                self.push(if text.starts_with(';') {
+                    text.trim_end()
+                } else {
+                    text
+                })
+            }
+            _ if node.children.is_empty() => self.push(self.cst.text(node)),
+            _ => self.container(node),
+        }
+    }
+
+    fn container(&mut self, node: &Node) {
+        let children = &node.children;
+        let mut indentation = Indentation {
+            kind: node.kind,
+            start: self.column,
+            open: children
+                .first()
+                .map_or(0, |child| self.cst.text(child).chars().count()),
+            symbol_head: false,
+            rule: None,
+            first_arg: None,
+        };
+        let mut elements = 0;
+        let mut head_line = self.line;
+        for (index, child) in children.iter().enumerate() {
+            if child.kind != NodeKind::Whitespace {
+                if !child.is_trivia() && child.kind != NodeKind::Punctuation {
+                    if elements == 0 && node.kind == NodeKind::List {
+                        head_line = self.line;
+                        if let Some(head) = self.head_symbol(child) {
+                            indentation.symbol_head = true;
+                            indentation.rule = self.config.rules.get(head).cloned();
+                        }
+                    } else if elements == 1 && self.line == head_line {
+                        indentation.first_arg = Some(self.column);
+                    }
+                    elements += 1;
+                }
+                self.node(child);
+                continue;
+            }
+            let previous = index.checked_sub(1).map(|previous| &children[previous]);
+            let next = children.get(index + 1);
+            let whitespace =
+                self.whitespace(node.kind, child, previous, next, indentation.of(elements));
+            self.push(&whitespace);
+        }
+    }
+
+    /// Name of the symbol `node` is, if it is one.
+    fn head_symbol(&self, node: &Node) -> Option<&str> {
+        let text = self.cst.text(node);
+        match SliceTokenizer::new(text).next_token() {
+            Ok(Some((SliceToken::Identifier(_), _))) if node.kind == NodeKind::Atom => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Whitespace `node` between `previous` and `next` inside a node of `kind` is
+    /// replaced with, `indent` being the indentation of the line if it breaks.
+    fn whitespace(
+        &self,
+        kind: NodeKind,
+        node: &Node,
+        previous: Option<&Node>,
+        next: Option<&Node>,
+        indent: usize,
+    ) -> String {
+        let text = self.cst.text(node);
+        let newlines = text.matches('\n').count();
+        let after_line_comment = previous.is_some_and(|previous| {
+            previous.kind == NodeKind::Comment && self.cst.text(previous).starts_with(';')
+        });
+        let after_opening = previous.is_some_and(|previous| previous.kind == NodeKind::Punctuation);
+        let before_closing = next.is_some_and(|next| next.kind == NodeKind::Punctuation);
+        let before_comment = next.is_some_and(|next| next.kind == NodeKind::Comment);
+
+        if kind == NodeKind::Root && (previous.is_none() || next.is_none()) {
+            return String::new();
+        }
+        if before_closing && !after_line_comment {
+            return String::new();
+        }
+        if after_opening && !before_comment {
+            let prefix = previous.map_or("", |previous| self.cst.text(previous));
+            let next_text = next.map_or("", |next| self.cst.text(next));
+            // `~ @x` must not turn into `~@x`, and a tag must stay apart from its form
+            let keep_apart =
+                kind == NodeKind::Tagged || (prefix == "~" && next_text.starts_with('@'));
+            if !keep_apart || newlines == 0 {
+                return if keep_apart { " " } else { "" }.to_owned();
+            }
+        }
+        let commas = if text.contains(',') && !after_line_comment {
+            ","
+        } else {
+            ""
+        };
+        if newlines == 0 {
+            return format!("{} ", commas);
+        }
+        format!(
+            "{}{}{}",
+            commas,
+            "\n".repeat(newlines.min(2)),
+            " ".repeat(indent)
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::arbitrary_code;
+    use proptest::prelude::*;
+    use std::fs;
+    use std::path::Path;
+
+    fn fmt(code: &str) -> Result<String, Error> {
+        format(code, &FormatConfig::default())
+    }
+
+    /// Expressions read from `code` in a form that shows metadata too.
+    fn expressions(code: &str) -> Option<String> {
+        Cst::parse(code)
+            .and_then(|cst| cst.expressions())
+            .ok()
+            .map(|forms| format!("{:?}", forms))
+    }
+
+    #[test]
+    fn should_normalize_spacing() -> Result<(), Error> {
+        // expect
+        assert_eq!("(f a b)\n", fmt("  ( f   a\tb )  ")?);
+        assert_eq!("{:a 1, :b [2 3]}\n", fmt("{ :a 1 ,  :b [ 2 3 ] }")?);
+        assert_eq!("'(a ~b ~@c #_d)\n", fmt("' ( a ~ b ~@ c #_ d )")?);
+        assert_eq!("(a ~ @b)\n", fmt("(a ~ @b)")?);
+        assert_eq!("#inst \"2020\"\n", fmt("#inst   \"2020\"")?);
+        assert_eq!("(a)\n\n(b)\n", fmt("(a)\n\n\n\n(b)\n\n")?);
+        Ok(())
+    }
+
+    #[test]
+    fn should_pull_closing_delimiters_up() -> Result<(), Error> {
+        // expect
+        assert_eq!("(f a\n   b)\n", fmt("(f a\nb\n)")?);
+        assert_eq!("(f a ; note\n   )\n", fmt("(f a ; note   \n)")?);
+        Ok(())
+    }
+
+    #[test]
+    fn should_align_arguments_and_elements() -> Result<(), Error> {
+        // expect
+        assert_eq!(
+            "(assoc m\n       :a 1\n       :b 2)\n",
+            fmt("(assoc m\n:a 1\n    :b 2)")?
+        );
+        assert_eq!("(assoc\n m\n :a)\n", fmt("(assoc\n   m\n   :a)")?);
+        assert_eq!("[1\n 2\n #{3\n   4}]\n", fmt("[1\n2\n#{3\n4}]")?);
+        assert_eq!("(1 2\n 3)\n", fmt("(1 2\n      3)")?);
+        Ok(())
+    }
+
+    #[test]
+    fn should_indent_bodies_by_rules() -> Result<(), Error> {
+        // expect
+        assert_eq!(
+            "(defn add\n    [a b]\n  (+ a b))\n",
+            fmt("(defn add\n[a b]\n(+ a b))")?
+        );
+        assert_eq!(
+            "(let [a 1\n      b 2]\n  (+ a b))\n",
+            fmt("(let [a 1\n b 2]\n     (+ a b))")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn should_use_configured_rules() -> Result<(), Error> {
+        // given
+        let config = FormatConfig::read("{with-file 1, let nil}")?;
+
+        // expect
+        assert_eq!(
+            "(with-file f\n  (read f))\n",
+            format("(with-file f\n(read f))", &config)?
+        );
+        assert_eq!("(let [a 1]\n     a)\n", format("(let [a 1]\na)", &config)?);
+        assert!(FormatConfig::read("{with-file :body}").is_err());
+        assert!(FormatConfig::read("[with-file 1]").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn should_keep_comments() -> Result<(), Error> {
+        // given
+        let code = "; header\n(f ; first\n a #| inline |# b)\n#_(ignored)\n";
+
+        // expect
+        assert_eq!(code, fmt(code)?);
+        Ok(())
+    }
+
+    #[test]
+    fn should_format_samples_idempotently() -> Result<(), Error> {
+        // given
+        let samples = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fmt");
+        let mut checked = 0;
+
+        for entry in fs::read_dir(samples)? {
+            let path = entry?.path();
+            let name = path.to_string_lossy().into_owned();
+            if !name.ends_with(".rp") || name.ends_with(".expected.rp") {
+                continue;
+            }
+            let source = fs::read_to_string(&path)?;
+            let expected = fs::read_to_string(name.replace(".rp", ".expected.rp"))?;
+
+            // when
+            let formatted = fmt(&source)?;
+
+            // then
+            assert_eq!(expected, formatted, "{}", name);
+            assert_eq!(formatted, fmt(&formatted)?, "{}", name);
+            assert_eq!(expressions(&source), expressions(&formatted), "{}", name);
+            checked += 1;
+        }
+        assert!(checked > 0);
+        Ok(())
+    }
+
+    const ALPHABET: &[&str] = &[
+        "(", ")", "[", "]", "{", "}", "#{", "'", "~", "@x", "^", "#_", "#tag", ":k", "x", "let",
+        "defn", "1", "\"s\"", "\"a\nb\"", "\\a", "; c\n", "#| b |#", " ", "  ", ",", "\n",
+        "\n\n\n",
+    ];
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(20_000))]
+
+        #[test]
+        fn should_keep_meaning_of_arbitrary_input(code in arbitrary_code(ALPHABET)) {
+            // when
+            let formatted = fmt(&code);
+
+            // then
+            if let Ok(formatted) = formatted {
+                prop_assert_eq!(expressions(&code), expressions(&formatted), "{:?}", code);
+                prop_assert_eq!(&formatted, &fmt(&formatted).unwrap(), "{:?}", code);
+            }
+        }
+    }
+}
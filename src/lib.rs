@@ -0,0 +1,18 @@
+// `failure`'s derive generates impls that newer compilers flag as non-local.
+#![allow(non_local_definitions)]
+
+#[macro_use]
+extern crate failure;
+
+pub mod collections;
+pub mod cst;
+pub mod eval;
+pub mod formatter;
+pub mod number;
+pub mod printer;
+pub mod reader;
+pub mod span;
+pub mod tokenizer;
+
+#[cfg(test)]
+mod testing;
@@ -1,48 +1,147 @@
-#[macro_use]
-extern crate failure;
+use failure::{format_err, Error};
+use rusty_parens::eval::{eval, print_options, register_builtins, Scope};
+use rusty_parens::formatter::{format, FormatConfig};
+use rusty_parens::printer::pprint_str;
+use rusty_parens::reader::{is_incomplete, Expression, Reader};
+use std::io::{self, Read, Write};
+use std::{env, fs, process};
 
-mod eval;
-mod reader;
-mod tokenizer;
+fn main() -> Result<(), Error> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("fmt") {
+        let code = fmt(&args[1..]).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            2
+        });
+        process::exit(code);
+    }
+    repl()
+}
 
-use crate::eval::Scope;
-use crate::reader::{Expression, Function, Reader};
-use eval::eval;
-use failure::Error;
-use std::io;
-use std::io::Write;
+const FMT_USAGE: &str = "usage: fmt [--check] [--config FILE] [FILE...]";
 
-fn main() -> Result<(), Error> {
+/// `fmt [--check] [--config FILE] [FILE...]`
+///
+/// Formats files in place, or standard input to standard output when no files
+/// are given. With `--check` nothing is written and the exit code is 1 if some
+/// input is not formatted. Indentation rules are read from the `--config` file.
+fn fmt(args: &[String]) -> Result<i32, Error> {
+    let mut check = false;
+    let mut config = FormatConfig::default();
+    let mut files = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--config" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| format_err!("--config expects a file"))?;
+                config = FormatConfig::read(&fs::read_to_string(path)?)
+                    .map_err(|error| format_err!("{}: {}", path, error))?;
+            }
+            option if option.starts_with("--") => {
+                return Err(format_err!("Unknown option: {}\n{}", option, FMT_USAGE));
+            }
+            _ => files.push(arg),
+        }
+    }
+
+    if files.is_empty() {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source)?;
+        let formatted = format(&source, &config)?;
+        if check {
+            return Ok(if formatted == source { 0 } else { 1 });
+        }
+        print!("{}", formatted);
+        return Ok(0);
+    }
+
+    let mut code = 0;
+    for path in files {
+        let formatted = fs::read_to_string(path)
+            .map_err(Error::from)
+            .and_then(|source| Ok((format(&source, &config)?, source)));
+        match formatted {
+            Ok((formatted, source)) if formatted == source => {}
+            Ok(_) if check => {
+                eprintln!("{}: not formatted", path);
+                code = code.max(1);
+            }
+            Ok((formatted, _)) => fs::write(path, formatted)?,
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                code = 2;
+            }
+        }
+    }
+    Ok(code)
+}
+
+fn repl() -> Result<(), Error> {
     println!("Rusty Parens");
     let mut scope = Scope::new();
 
-    scope.put(&"+", Expression::Fn(Function::Native(integer_add)));
+    register_builtins(&mut scope);
 
     loop {
-        let expr = read()?;
-        let result = eval(&mut scope, &expr);
-        match result {
-            Ok(result) => print(result),
-            Err(error) => eprintln!("{}", error),
+        let exprs = match read() {
+            Ok(Some(exprs)) => exprs,
+            Ok(None) => return Ok(()),
+            Err(error) => {
+                eprintln!("{}", error);
+                continue;
+            }
+        };
+        for expr in exprs {
+            match eval(&mut scope, &expr) {
+                Ok(result) => print(result, &scope),
+                Err(error) => eprintln!("{}", error),
+            }
         }
     }
 }
 
-fn read() -> Result<Expression, Error> {
-    print!("> ");
-    io::stdout().flush()?;
+/// Reads lines until they form complete expressions, returns all of them or
+/// `None` at the end of input.
+fn read() -> Result<Option<Vec<Expression>>, Error> {
     let mut buffer = String::new();
-    io::stdin().read_line(&mut buffer)?;
-    Reader::from_string(&buffer).read()
+    loop {
+        print!("{}", if buffer.is_empty() { "> " } else { ". " });
+        io::stdout().flush()?;
+        if io::stdin().read_line(&mut buffer)? == 0 {
+            return Ok(None);
+        }
+        match Reader::from_string(&buffer).read_all() {
+            Ok(exprs) if exprs.is_empty() => buffer.clear(),
+            Ok(exprs) => return Ok(Some(exprs)),
+            Err(error) if is_incomplete(&error) => continue,
+            Err(error) => return Err(error),
+        }
+    }
 }
 
-fn print(expr: Expression) {
-    println!("{}", expr)
+fn print(expr: Expression, scope: &Scope) {
+    println!("{}", pprint_str(&expr, &print_options(scope)))
 }
 
-fn integer_add(exprs: &[Expression]) -> Result<Expression, Error> {
-    match exprs {
-        [Expression::Integer(a), Expression::Integer(b)] => Ok(Expression::Integer(a + b)),
-        _ => Ok(Expression::String("Incompatible types".to_owned())),
+#[cfg(test)]
+mod test {
+    use super::fmt;
+
+    #[test]
+    fn should_reject_unknown_options_with_usage() {
+        // given
+        let args = ["--chek".to_owned(), "foo.rp".to_owned()];
+
+        // when
+        let error = fmt(&args).err().unwrap();
+
+        // then
+        assert_eq!(
+            "Unknown option: --chek\nusage: fmt [--check] [--config FILE] [FILE...]",
+            error.to_string()
+        );
     }
 }
@@ -0,0 +1,23 @@
+use crate::reader::Expression;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::ToPrimitive;
+
+pub mod decimal;
+
+/// Demotes big integer to `Expression::Integer` when it fits in `i64`.
+pub fn normalize_bigint(value: BigInt) -> Expression {
+    match value.to_i64() {
+        Some(value) => Expression::Integer(value),
+        None => Expression::BigInt(value),
+    }
+}
+
+/// Turns ratio with denominator of 1 into an integer.
+pub fn normalize_ratio(value: BigRational) -> Expression {
+    if value.is_integer() {
+        normalize_bigint(value.to_integer())
+    } else {
+        Expression::Ratio(value)
+    }
+}
@@ -0,0 +1,389 @@
+use failure::Error;
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{pow, Signed, ToPrimitive, Zero};
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+/// Largest scale a decimal can be rounded to, larger ones take too long to compute.
+pub const MAX_SCALE: u32 = 1000;
+
+#[derive(Debug, Fail)]
+pub enum DecimalError {
+    #[fail(display = "Invalid decimal: {}", _0)]
+    InvalidDecimal(String),
+
+    #[fail(
+        display = "Unknown rounding mode: {} (expected up, down, ceiling, floor, half-up, half-down or half-even)",
+        _0
+    )]
+    UnknownRoundingMode(String),
+
+    #[fail(display = "Division has no exact decimal result, give a scale and rounding mode")]
+    NonTerminating,
+
+    #[fail(display = "Division by zero")]
+    DivisionByZero,
+}
+
+/// How to round a value that does not fit in the requested scale.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RoundingMode {
+    /// Away from zero.
+    Up,
+    /// Towards zero.
+    Down,
+    Ceiling,
+    Floor,
+    HalfUp,
+    HalfDown,
+    /// To the nearest neighbour, ties go to the even one.
+    HalfEven,
+}
+
+impl FromStr for RoundingMode {
+    type Err = DecimalError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "up" => RoundingMode::Up,
+            "down" => RoundingMode::Down,
+            "ceiling" => RoundingMode::Ceiling,
+            "floor" => RoundingMode::Floor,
+            "half-up" => RoundingMode::HalfUp,
+            "half-down" => RoundingMode::HalfDown,
+            "half-even" => RoundingMode::HalfEven,
+            other => return Err(DecimalError::UnknownRoundingMode(other.to_owned())),
+        })
+    }
+}
+
+impl RoundingMode {
+    /// Rounds `numerator / denominator` to an integer.
+    fn round(self, numerator: &BigInt, denominator: &BigInt) -> BigInt {
+        let (numerator, denominator) = if denominator.is_negative() {
+            (-numerator, -denominator)
+        } else {
+            (numerator.clone(), denominator.clone())
+        };
+        let (floor, remainder) = numerator.div_mod_floor(&denominator);
+        if remainder.is_zero() {
+            return floor;
+        }
+        let negative = numerator.is_negative();
+        let round_up = match self {
+            RoundingMode::Floor => false,
+            RoundingMode::Ceiling => true,
+            RoundingMode::Down => negative,
+            RoundingMode::Up => !negative,
+            half => match (remainder * 2u32).cmp(&denominator) {
+                Ordering::Less => false,
+                Ordering::Greater => true,
+                Ordering::Equal => match half {
+                    RoundingMode::HalfUp => !negative,
+                    RoundingMode::HalfDown => negative,
+                    _ => floor.is_odd(),
+                },
+            },
+        };
+        if round_up {
+            floor + 1
+        } else {
+            floor
+        }
+    }
+}
+
+/// Fixed-point decimal number: `unscaled * 10^-scale`.
+///
+/// Arithmetic is exact. Decimals are equal when they have the same value, even if
+/// their scales differ, so `1.0M` equals `1.00M`.
+#[derive(Debug, Clone)]
+pub struct Decimal {
+    unscaled: BigInt,
+    scale: u32,
+}
+
+impl Decimal {
+    pub fn new(unscaled: BigInt, scale: u32) -> Self {
+        Self { unscaled, scale }
+    }
+
+    pub fn from_integer(value: BigInt) -> Self {
+        Self::new(value, 0)
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    pub fn add(&self, other: &Decimal) -> Decimal {
+        let scale = self.scale.max(other.scale);
+        Decimal::new(self.rescaled(scale) + other.rescaled(scale), scale)
+    }
+
+    pub fn sub(&self, other: &Decimal) -> Decimal {
+        let scale = self.scale.max(other.scale);
+        Decimal::new(self.rescaled(scale) - other.rescaled(scale), scale)
+    }
+
+    pub fn mul(&self, other: &Decimal) -> Decimal {
+        Decimal::new(&self.unscaled * &other.unscaled, self.scale + other.scale)
+    }
+
+    pub fn neg(&self) -> Decimal {
+        Decimal::new(-&self.unscaled, self.scale)
+    }
+
+    /// Exact quotient, keeping at least the scale of `self`. Fails when the result
+    /// has no finite decimal expansion.
+    pub fn div(&self, other: &Decimal) -> Result<Decimal, Error> {
+        if other.unscaled.is_zero() {
+            return Err(DecimalError::DivisionByZero.into());
+        }
+        let quotient = self.to_ratio() / other.to_ratio();
+        let scale = terminating_scale(quotient.denom()).ok_or(DecimalError::NonTerminating)?;
+        Ok(Decimal::from_ratio(
+            &quotient,
+            scale.max(self.scale),
+            RoundingMode::Down,
+        ))
+    }
+
+    /// Quotient rounded to `scale` digits after the decimal point.
+    pub fn div_rounded(
+        &self,
+        other: &Decimal,
+        scale: u32,
+        mode: RoundingMode,
+    ) -> Result<Decimal, Error> {
+        if other.unscaled.is_zero() {
+            return Err(DecimalError::DivisionByZero.into());
+        }
+        Ok(Decimal::from_ratio(
+            &(self.to_ratio() / other.to_ratio()),
+            scale,
+            mode,
+        ))
+    }
+
+    pub fn round(&self, scale: u32, mode: RoundingMode) -> Decimal {
+        Decimal::from_ratio(&self.to_ratio(), scale, mode)
+    }
+
+    pub fn from_ratio(value: &BigRational, scale: u32, mode: RoundingMode) -> Decimal {
+        let numerator = value.numer() * ten_to(scale);
+        Decimal::new(mode.round(&numerator, value.denom()), scale)
+    }
+
+    /// Exact decimal for a ratio with a finite decimal expansion.
+    pub fn from_ratio_exact(value: &BigRational) -> Option<Decimal> {
+        let scale = terminating_scale(value.denom())?;
+        Some(Decimal::from_ratio(value, scale, RoundingMode::Down))
+    }
+
+    pub fn to_ratio(&self) -> BigRational {
+        BigRational::new(self.unscaled.clone(), ten_to(self.scale))
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.to_ratio().to_f64().unwrap_or(f64::NAN)
+    }
+
+    /// Same value with trailing zeros after the decimal point dropped.
+    fn normalized(&self) -> Decimal {
+        let mut unscaled = self.unscaled.clone();
+        let mut scale = self.scale;
+        while scale > 0 && (&unscaled % 10u32).is_zero() {
+            unscaled /= 10;
+            scale -= 1;
+        }
+        Decimal::new(unscaled, scale)
+    }
+
+    /// Unscaled value at a scale not smaller than the current one.
+    fn rescaled(&self, scale: u32) -> BigInt {
+        &self.unscaled * ten_to(scale - self.scale)
+    }
+}
+
+fn ten_to(exponent: u32) -> BigInt {
+    pow(BigInt::from(10), exponent as usize)
+}
+
+/// Number of decimal digits needed to represent `1 / denominator` exactly, or
+/// `None` if the expansion does not terminate.
+fn terminating_scale(denominator: &BigInt) -> Option<u32> {
+    let mut rest = denominator.abs();
+    let (mut twos, mut fives) = (0, 0);
+    while rest.is_even() && !rest.is_zero() {
+        rest /= 2;
+        twos += 1;
+    }
+    while (&rest % 5u32).is_zero() && !rest.is_zero() {
+        rest /= 5;
+        fives += 1;
+    }
+    if rest == BigInt::from(1) {
+        Some(u32::max(twos, fives))
+    } else {
+        None
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Decimal) -> bool {
+        let scale = self.scale.max(other.scale);
+        self.rescaled(scale) == other.rescaled(scale)
+    }
+}
+
+impl Hash for Decimal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let normalized = self.normalized();
+        normalized.unscaled.hash(state);
+        normalized.scale.hash(state);
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = DecimalError;
+
+    /// Parses plain decimal notation like `-12.50`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || DecimalError::InvalidDecimal(s.to_owned());
+        let (whole, fraction) = match s.find('.') {
+            Some(point) => (&s[..point], &s[point + 1..]),
+            None => (s, ""),
+        };
+        let digits = match whole {
+            "" | "+" | "-" if !fraction.is_empty() => format!("{}0{}", whole, fraction),
+            _ => format!("{}{}", whole, fraction),
+        };
+        let unscaled = digits.parse::<BigInt>().map_err(|_| invalid())?;
+        Ok(Decimal::new(unscaled, fraction.len() as u32))
+    }
+}
+
+impl Display for Decimal {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        let digits = self.unscaled.abs().to_string();
+        let scale = self.scale as usize;
+        let digits = if digits.len() <= scale {
+            format!("{}{}", "0".repeat(scale - digits.len() + 1), digits)
+        } else {
+            digits
+        };
+        if self.unscaled.sign() == Sign::Minus {
+            f.write_str("-")?;
+        }
+        let point = digits.len() - scale;
+        f.write_str(&digits[..point])?;
+        if scale > 0 {
+            write!(f, ".{}", &digits[point..])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn decimal(s: &str) -> Decimal {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn should_parse_and_display_exactly() {
+        for text in &[
+            "12.50",
+            "-0.05",
+            "0.000",
+            "7",
+            "-3",
+            "123456789012345678901234.5",
+        ] {
+            // expect
+            assert_eq!(*text, decimal(text).to_string());
+        }
+        assert_eq!("0.5", decimal(".5").to_string());
+    }
+
+    #[test]
+    fn should_do_exact_arithmetic() {
+        // expect
+        assert_eq!("0.30", decimal("0.10").add(&decimal("0.2")).to_string());
+        assert_eq!("-0.1", decimal("0.1").sub(&decimal("0.2")).to_string());
+        assert_eq!("1.2500", decimal("12.50").mul(&decimal("0.10")).to_string());
+    }
+
+    #[test]
+    fn should_compare_values_regardless_of_scale() {
+        // expect
+        assert_eq!(decimal("1.0"), decimal("1.00"));
+        assert_ne!(decimal("1.0"), decimal("1.01"));
+    }
+
+    #[test]
+    fn should_hash_equal_values_equally() {
+        // given
+        let hash = |value: &Decimal| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        // expect
+        assert_eq!(hash(&decimal("1.50")), hash(&decimal("1.5")));
+        assert_eq!(hash(&decimal("0.000")), hash(&decimal("0")));
+        assert_ne!(hash(&decimal("1.5")), hash(&decimal("15")));
+    }
+
+    #[test]
+    fn should_divide_exactly_or_fail() -> Result<(), Error> {
+        // expect
+        assert_eq!("2.50", decimal("10.00").div(&decimal("4"))?.to_string());
+        assert_eq!("0.125", decimal("1").div(&decimal("8"))?.to_string());
+        assert!(decimal("1.00").div(&decimal("3")).is_err());
+        assert!(decimal("1.00").div(&decimal("0.0")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn should_round_with_every_mode() {
+        use RoundingMode::*;
+        let cases = [
+            (Up, ["3", "2", "-2", "-3"]),
+            (Down, ["2", "2", "-2", "-2"]),
+            (Ceiling, ["3", "2", "-2", "-2"]),
+            (Floor, ["2", "2", "-2", "-3"]),
+            (HalfUp, ["3", "2", "-2", "-3"]),
+            (HalfDown, ["2", "2", "-2", "-2"]),
+            (HalfEven, ["2", "2", "-2", "-2"]),
+        ];
+        for (mode, expected) in &cases {
+            for (value, expected) in ["2.5", "2.0", "-2.0", "-2.5"].iter().zip(expected) {
+                // expect
+                assert_eq!(
+                    *expected,
+                    decimal(value).round(0, *mode).to_string(),
+                    "rounding {} {:?}",
+                    value,
+                    mode
+                );
+            }
+        }
+        assert_eq!("4", decimal("3.5").round(0, HalfEven).to_string());
+        assert_eq!(
+            "0.33",
+            decimal("1.00")
+                .div_rounded(&decimal("3"), 2, HalfUp)
+                .unwrap()
+                .to_string()
+        );
+    }
+}
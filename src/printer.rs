@@ -0,0 +1,259 @@
+use crate::reader::Expression;
+use crate::tokenizer::CHAR_NAMES;
+use std::fmt::{Display, Formatter, Write};
+use std::iter::once;
+
+pub(crate) use self::pretty::BODY_FORMS;
+pub use self::pretty::{pprint_str, PrettyOptions};
+
+mod pretty;
+
+/// Prints `expr` so that the `Reader` reads it back as an equal expression.
+///
+/// Strings are quoted and escaped, characters are written as literals. Functions
+/// and non-finite floats have no literal: they are printed as `#<function>`,
+/// `##NaN`, `##Inf` or `##-Inf`, which read back as identifiers, not as the
+/// original value. Metadata is not printed.
+pub fn pr_str(expr: &Expression) -> String {
+    Printed {
+        expr,
+        readably: true,
+    }
+    .to_string()
+}
+
+/// Prints `expr` for people to read: strings and characters are printed as they
+/// are, without quotes or escapes.
+pub fn print_str(expr: &Expression) -> String {
+    Printed {
+        expr,
+        readably: false,
+    }
+    .to_string()
+}
+
+struct Printed<'a> {
+    expr: &'a Expression,
+    readably: bool,
+}
+
+impl Printed<'_> {
+    fn nested<'b>(&self, expr: &'b Expression) -> Printed<'b> {
+        Printed {
+            expr,
+            readably: self.readably,
+        }
+    }
+
+    fn write_sequence<'b>(
+        &self,
+        f: &mut Formatter,
+        open: &str,
+        values: impl Iterator<Item = &'b Expression>,
+        close: &str,
+    ) -> Result<(), std::fmt::Error> {
+        f.write_str(open)?;
+        for (index, value) in values.enumerate() {
+            if index > 0 {
+                f.write_char(' ')?;
+            }
+            Display::fmt(&self.nested(value), f)?;
+        }
+        f.write_str(close)
+    }
+}
+
+impl Display for Printed<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        match self.expr {
+            Expression::Float(value) => write_float(f, *value),
+            Expression::Integer(value) => write!(f, "{}", value),
+            Expression::BigInt(value) => write!(f, "{}", value),
+            Expression::Ratio(value) => write!(f, "{}", value),
+            Expression::Decimal(value) => write!(f, "{}M", value),
+            Expression::Fn(_) => f.write_str("#<function>"),
+            Expression::Identifier(name) => f.write_str(name),
+            Expression::Keyword(name) => write!(f, ":{}", name),
+            Expression::Bool(value) => write!(f, "{}", value),
+            Expression::Nil => f.write_str("nil"),
+            Expression::Char(c) if self.readably => write_char(f, *c),
+            Expression::Char(c) => f.write_char(*c),
+            Expression::String(text) if self.readably => write_string(f, text),
+            Expression::String(text) => f.write_str(text),
+            Expression::List(values) => self.write_sequence(f, "(", values.iter(), ")"),
+            Expression::Vector(values) => self.write_sequence(f, "[", values.iter(), "]"),
+            Expression::Map(map) => self.write_sequence(
+                f,
+                "{",
+                map.iter().flat_map(|(k, v)| once(k).chain(once(v))),
+                "}",
+            ),
+            Expression::Set(set) => self.write_sequence(f, "#{", set.iter(), "}"),
+            Expression::WithMeta(form, _) => Display::fmt(&self.nested(form), f),
+        }
+    }
+}
+
+/// Writes float so that it reads back as a float, `1.0` rather than `1`.
+fn write_float(f: &mut Formatter, value: f64) -> Result<(), std::fmt::Error> {
+    if value.is_nan() {
+        f.write_str("##NaN")
+    } else if value.is_infinite() {
+        f.write_str(if value > 0.0 { "##Inf" } else { "##-Inf" })
+    } else {
+        write!(f, "{:?}", value)
+    }
+}
+
+/// Writes character the way it is written in a character literal.
+pub(crate) fn write_char(f: &mut Formatter, c: char) -> Result<(), std::fmt::Error> {
+    if !c.is_whitespace() && !c.is_control() {
+        return write!(f, "\\{}", c);
+    }
+    match CHAR_NAMES.iter().find(|(_, named)| *named == c) {
+        Some((name, _)) => write!(f, "\\{}", name),
+        None if (c as u32) <= 0xFFFF => write!(f, "\\u{:04X}", c as u32),
+        None => write!(f, "\\{}", c),
+    }
+}
+
+/// Writes string the way it is written in a string literal.
+fn write_string(f: &mut Formatter, text: &str) -> Result<(), std::fmt::Error> {
+    f.write_char('"')?;
+    for c in text.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\t' => f.write_str("\\t")?,
+            '\r' => f.write_str("\\r")?,
+            '\0' => f.write_str("\\0")?,
+            '\u{8}' => f.write_str("\\b")?,
+            '\u{c}' => f.write_str("\\f")?,
+            c if c.is_control() => write!(f, "\\u{{{:X}}}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::collections::{Map, Set};
+    use crate::number::decimal::Decimal;
+    use crate::number::{normalize_bigint, normalize_ratio};
+    use crate::reader::{Function, Reader};
+    use failure::Error;
+    use num_bigint::BigInt;
+    use num_rational::BigRational;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    fn read(code: &str) -> Result<Expression, Error> {
+        Reader::from_string(code).read()
+    }
+
+    #[test]
+    fn should_print_strings_readably() -> Result<(), Error> {
+        // given
+        let expr = read("(a \"b c\" \"say \\\"hi\\\"\\n\\\\\" \"\\u{7}\")")?;
+
+        // expect
+        assert_eq!(
+            "(a \"b c\" \"say \\\"hi\\\"\\n\\\\\" \"\\u{7}\")",
+            pr_str(&expr)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn should_print_strings_and_characters_as_they_are_for_people() -> Result<(), Error> {
+        // given
+        let expr = read("(a \"b c\" \\d \\space :e [1.0 {\"f\" #{nil}}])")?;
+
+        // expect
+        assert_eq!("(a b c d   :e [1.0 {f #{nil}}])", print_str(&expr));
+        Ok(())
+    }
+
+    #[test]
+    fn should_print_nested_collections_and_literals() -> Result<(), Error> {
+        // given
+        let expr = read("[:k true nil \\a \\newline 1/3 2.50M 1.0 1e300 [] {:m (#{})}]")?;
+
+        // expect
+        assert_eq!(
+            "[:k true nil \\a \\newline 1/3 2.50M 1.0 1e300 [] {:m (#{})}]",
+            pr_str(&expr)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn should_print_values_without_literals() {
+        // given
+        let native: fn(&[Expression]) -> Result<Expression, Error> = |_| Ok(Expression::Nil);
+
+        // expect
+        assert_eq!("##NaN", pr_str(&Expression::Float(f64::NAN)));
+        assert_eq!("##-Inf", pr_str(&Expression::Float(f64::NEG_INFINITY)));
+        assert_eq!(
+            "#<function>",
+            pr_str(&Expression::Fn(Function::Native(native)))
+        );
+    }
+
+    fn symbol() -> impl Strategy<Value = String> {
+        "[a-z*!_?<>=][a-z0-9*!_?<>=+-]{0,8}"
+    }
+
+    fn atom() -> impl Strategy<Value = Expression> {
+        prop_oneof![
+            any::<i64>().prop_map(Expression::Integer),
+            any::<i128>().prop_map(|n| normalize_bigint(BigInt::from(n))),
+            (any::<i64>(), 1..i64::MAX)
+                .prop_map(|(n, d)| normalize_ratio(BigRational::new(n.into(), d.into()))),
+            any::<f64>()
+                .prop_filter("non-finite floats are not readable", |v| v.is_finite())
+                .prop_map(Expression::Float),
+            (any::<i64>(), 0..12u32)
+                .prop_map(|(n, scale)| Expression::Decimal(Decimal::new(n.into(), scale))),
+            symbol()
+                .prop_filter("literal names", |name| {
+                    !["true", "false", "nil"].contains(&name.as_str())
+                })
+                .prop_map(Expression::Identifier),
+            symbol().prop_map(Expression::Keyword),
+            any::<bool>().prop_map(Expression::Bool),
+            Just(Expression::Nil),
+            any::<char>().prop_map(Expression::Char),
+            any::<String>().prop_map(Expression::String),
+        ]
+    }
+
+    pub(super) fn expression() -> impl Strategy<Value = Expression> {
+        atom().prop_recursive(4, 64, 8, |inner| {
+            prop_oneof![
+                vec(inner.clone(), 0..8).prop_map(Expression::List),
+                vec(inner.clone(), 0..8).prop_map(Expression::Vector),
+                vec((inner.clone(), inner.clone()), 0..8)
+                    .prop_map(|entries| Expression::Map(entries.into_iter().collect::<Map>())),
+                vec(inner, 0..8)
+                    .prop_map(|elements| Expression::Set(elements.into_iter().collect::<Set>())),
+            ]
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn should_read_back_printed_expressions(expr in expression()) {
+            // when
+            let printed = pr_str(&expr);
+
+            // then
+            let read_back = read(&printed).map_err(|error| TestCaseError::fail(error.to_string()))?;
+            prop_assert_eq!(expr, read_back, "printed as {}", printed);
+        }
+    }
+}
@@ -0,0 +1,394 @@
+use super::pr_str;
+use crate::reader::Expression;
+use std::iter::once;
+
+/// Settings of the pretty printer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrettyOptions {
+    /// Column the printer tries not to go past.
+    pub width: usize,
+    /// Number of elements printed of each collection, the rest is printed as `...`.
+    pub print_length: Option<usize>,
+    /// Depth of nested collections printed, deeper ones are printed as `#`.
+    pub print_level: Option<usize>,
+}
+
+impl Default for PrettyOptions {
+    fn default() -> Self {
+        Self {
+            width: 80,
+            print_length: None,
+            print_level: None,
+        }
+    }
+}
+
+/// Prints `expr` readably, breaking collections that do not fit in the width into
+/// lines. Bodies of forms like `let`, `fn` or `if` are indented by two spaces,
+/// other elements are aligned with each other.
+pub fn pprint_str(expr: &Expression, options: &PrettyOptions) -> String {
+    let doc = Builder { options }.doc(expr, 0);
+    layout(&doc, options.width)
+}
+
+/// Layouts a document can be printed with, as in Wadler's "A prettier printer".
+#[derive(Debug)]
+enum Doc {
+    Text(String),
+    /// Space when the enclosing group fits on the line, line break otherwise.
+    Line,
+    Concat(Vec<Doc>),
+    /// Lines inside break to the enclosing indentation plus the given number.
+    Nest(usize, Box<Doc>),
+    /// Lines inside break to the column the document starts at.
+    Align(Box<Doc>),
+    /// Lines inside break only if the whole group does not fit on the line.
+    Group(Box<Doc>),
+}
+
+fn text(text: &str) -> Doc {
+    Doc::Text(text.to_owned())
+}
+
+fn group(docs: Vec<Doc>) -> Doc {
+    Doc::Group(Box::new(Doc::Concat(docs)))
+}
+
+fn align(doc: Doc) -> Doc {
+    Doc::Align(Box::new(doc))
+}
+
+/// `docs` separated by lines.
+fn lines(docs: Vec<Doc>) -> Doc {
+    let mut joined = vec![];
+    for (index, doc) in docs.into_iter().enumerate() {
+        if index > 0 {
+            joined.push(Doc::Line);
+        }
+        joined.push(doc);
+    }
+    Doc::Concat(joined)
+}
+
+/// Forms whose first arguments stay on the line of the head, with the number of
+/// those arguments. Remaining arguments are the body.
+pub(crate) const BODY_FORMS: &[(&str, usize)] = &[
+    ("def", 1),
+    ("defn", 2),
+    ("defmacro", 2),
+    ("do", 0),
+    ("fn", 1),
+    ("if", 1),
+    ("lambda", 1),
+    ("let", 1),
+    ("loop", 1),
+    ("when", 1),
+    ("when-not", 1),
+];
+
+struct Builder<'a> {
+    options: &'a PrettyOptions,
+}
+
+impl Builder<'_> {
+    fn doc(&self, expr: &Expression, depth: usize) -> Doc {
+        let expr = expr.without_meta();
+        let is_collection = matches!(
+            expr,
+            Expression::List(_) | Expression::Vector(_) | Expression::Map(_) | Expression::Set(_)
+        );
+        if is_collection && self.too_deep(depth) {
+            return text("#");
+        }
+        match expr {
+            Expression::List(items) => self.list(items, depth),
+            Expression::Vector(items) => self.sequence("[", self.elements(items, depth), "]"),
+            Expression::Set(set) => {
+                let elements: Vec<_> = set.iter().cloned().collect();
+                self.sequence("#{", self.elements(&elements, depth), "}")
+            }
+            Expression::Map(map) => {
+                let entries = map
+                    .iter()
+                    .map(|(key, value)| {
+                        Doc::Concat(vec![
+                            self.doc(key, depth + 1),
+                            text(" "),
+                            self.doc(value, depth + 1),
+                        ])
+                    })
+                    .collect();
+                self.sequence("{", self.limited(entries), "}")
+            }
+            atom => Doc::Text(pr_str(atom)),
+        }
+    }
+
+    fn too_deep(&self, depth: usize) -> bool {
+        self.options.print_level.is_some_and(|level| depth >= level)
+    }
+
+    fn elements(&self, items: &[Expression], depth: usize) -> Vec<Doc> {
+        self.limited(items.iter().map(|item| self.doc(item, depth + 1)).collect())
+    }
+
+    /// `docs` cut to the print length, with `...` standing for the rest.
+    fn limited(&self, mut docs: Vec<Doc>) -> Vec<Doc> {
+        if let Some(length) = self.options.print_length {
+            if docs.len() > length {
+                docs.truncate(length);
+                docs.push(text("..."));
+            }
+        }
+        docs
+    }
+
+    /// Elements aligned with the first one.
+    fn sequence(&self, open: &str, docs: Vec<Doc>, close: &str) -> Doc {
+        group(vec![text(open), align(lines(docs)), text(close)])
+    }
+
+    fn list(&self, items: &[Expression], depth: usize) -> Doc {
+        let head = match items.first().map(Expression::without_meta) {
+            Some(Expression::Identifier(name)) => name.as_str(),
+            _ => return self.sequence("(", self.elements(items, depth), ")"),
+        };
+        let binds = matches!(head, "let" | "loop") && !self.too_deep(depth + 1);
+        let docs = items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| match item.without_meta() {
+                Expression::Vector(bindings) if binds && index == 1 => {
+                    self.bindings(bindings, depth + 1)
+                }
+                item => self.doc(item, depth + 1),
+            })
+            .collect();
+        let mut docs = self.limited(docs).into_iter();
+        let head_doc = docs.next().expect("list has a head");
+        let args: Vec<Doc> = docs.collect();
+        let body_form = BODY_FORMS.iter().find(|(name, _)| *name == head);
+        match body_form {
+            Some((_, count)) => {
+                // a named `fn` keeps its parameters next to the name
+                let named_fn = matches!(head, "fn" | "lambda")
+                    && matches!(
+                        items.get(1).map(Expression::without_meta),
+                        Some(Expression::Identifier(_))
+                    );
+                let count = (count + named_fn as usize).min(args.len());
+                let mut args = args.into_iter();
+                let first: Vec<Doc> = args.by_ref().take(count).collect();
+                let body: Vec<Doc> = args
+                    .flat_map(|doc| once(Doc::Line).chain(once(doc)))
+                    .collect();
+                let mut parts = vec![text("("), head_doc];
+                if !first.is_empty() {
+                    parts.push(text(" "));
+                    parts.push(Doc::Group(Box::new(align(lines(first)))));
+                }
+                parts.push(Doc::Nest(2, Box::new(Doc::Concat(body))));
+                parts.push(text(")"));
+                group(parts)
+            }
+            None if args.is_empty() => group(vec![text("("), head_doc, text(")")]),
+            None => group(vec![
+                text("("),
+                head_doc,
+                text(" "),
+                align(lines(args)),
+                text(")"),
+            ]),
+        }
+    }
+
+    /// Binding vector of `let` or `loop`, one name and value pair per line.
+    fn bindings(&self, items: &[Expression], depth: usize) -> Doc {
+        let docs = self.elements(items, depth);
+        let mut pairs = vec![];
+        let mut docs = docs.into_iter();
+        while let Some(name) = docs.next() {
+            pairs.push(match docs.next() {
+                Some(value) => Doc::Concat(vec![name, text(" "), value]),
+                None => name,
+            });
+        }
+        self.sequence("[", pairs, "]")
+    }
+}
+
+/// Prints `doc`, breaking the lines of groups that do not fit in `width`.
+fn layout(doc: &Doc, width: usize) -> String {
+    let mut output = String::new();
+    let mut column = 0;
+    // documents left to print with their indentation and whether they are flat
+    let mut stack: Vec<(usize, bool, &Doc)> = vec![(0, false, doc)];
+    while let Some((indent, flat, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(text) => {
+                output.push_str(text);
+                column += text.chars().count();
+            }
+            Doc::Line if flat => {
+                output.push(' ');
+                column += 1;
+            }
+            Doc::Line => {
+                output.push('\n');
+                output.extend(std::iter::repeat_n(' ', indent));
+                column = indent;
+            }
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (indent, flat, doc))),
+            Doc::Nest(extra, doc) => stack.push((indent + extra, flat, doc)),
+            Doc::Align(doc) => stack.push((column, flat, doc)),
+            Doc::Group(doc) => {
+                let flat = flat || fits(width as isize - column as isize, doc, &stack);
+                stack.push((indent, flat, doc));
+            }
+        }
+    }
+    output
+}
+
+/// Whether `doc` printed flat, followed by what comes after it up to the next line
+/// break, fits in `remaining` columns.
+fn fits(mut remaining: isize, doc: &Doc, rest: &[(usize, bool, &Doc)]) -> bool {
+    let mut pending = vec![(true, doc)];
+    let mut rest = rest.iter().rev();
+    loop {
+        if remaining < 0 {
+            return false;
+        }
+        let (flat, doc) = match pending.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some((_, flat, doc)) => (*flat, *doc),
+                None => return true,
+            },
+        };
+        match doc {
+            Doc::Text(text) => remaining -= text.chars().count() as isize,
+            Doc::Line if flat => remaining -= 1,
+            Doc::Line => return true,
+            Doc::Concat(docs) => pending.extend(docs.iter().rev().map(|doc| (flat, doc))),
+            Doc::Nest(_, doc) | Doc::Align(doc) | Doc::Group(doc) => pending.push((flat, doc)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::expression;
+    use super::*;
+    use crate::reader::Reader;
+    use failure::Error;
+    use proptest::prelude::*;
+
+    fn pprint(code: &str, width: usize) -> Result<String, Error> {
+        let options = PrettyOptions {
+            width,
+            ..PrettyOptions::default()
+        };
+        Ok(pprint_str(&Reader::from_string(code).read()?, &options))
+    }
+
+    #[test]
+    fn should_print_on_one_line_when_it_fits() -> Result<(), Error> {
+        // given
+        let code = "(f \"a\" [1 2] {:b #{3}})";
+
+        // expect
+        assert_eq!(code, pprint(code, 80)?);
+        Ok(())
+    }
+
+    #[test]
+    fn should_align_elements_of_broken_collections() -> Result<(), Error> {
+        // expect
+        assert_eq!(
+            "[:alpha\n :beta\n {:gamma 1\n  :delta [1 2 3]}]",
+            pprint("[:alpha :beta {:gamma 1 :delta [1 2 3]}]", 20)?
+        );
+        assert_eq!(
+            "(assoc record\n       :name\n       \"x\"\n       :tags\n       #{:a})",
+            pprint("(assoc record :name \"x\" :tags #{:a})", 30)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn should_indent_bodies_of_special_forms() -> Result<(), Error> {
+        // expect
+        assert_eq!(
+            "(let [a 1\n      b (+ a 1)]\n  (f a)\n  (g b))",
+            pprint("(let [a 1 b (+ a 1)] (f a) (g b))", 16)?
+        );
+        assert_eq!(
+            "(fn add [a b]\n  (+ a b))",
+            pprint("(fn add [a b] (+ a b))", 15)?
+        );
+        assert_eq!(
+            "(if (ready? x)\n  (start x)\n  (wait x))",
+            pprint("(if (ready? x) (start x) (wait x))", 20)?
+        );
+        assert_eq!(
+            "(defn add [a b]\n  (+ a b))",
+            pprint("(^:m defn add [a b] (+ a b))", 15)?
+        );
+        assert_eq!(
+            "(fn add [a b]\n  (+ a b))",
+            pprint("(fn ^:m add [a b] (+ a b))", 15)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn should_break_inner_groups_first() -> Result<(), Error> {
+        // expect
+        assert_eq!(
+            "(defn f [x]\n  (g [1 2 3]\n     [4 5 6]))",
+            pprint("(defn f [x] (g [1 2 3] [4 5 6]))", 16)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn should_limit_length_and_depth() -> Result<(), Error> {
+        // given
+        let expr = Reader::from_string("[1 [2 [3 [4]]] {:a 1 :b 2 :c 3} 5 6]").read()?;
+        let options = PrettyOptions {
+            print_length: Some(3),
+            print_level: Some(2),
+            ..PrettyOptions::default()
+        };
+
+        // expect
+        assert_eq!(
+            "[1 [2 #] {:a 1 :b 2 :c 3} ...]",
+            pprint_str(&expr, &options)
+        );
+        let options = PrettyOptions {
+            print_level: Some(0),
+            ..PrettyOptions::default()
+        };
+        assert_eq!("#", pprint_str(&expr, &options));
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn should_read_back_pretty_printed_expressions(expr in expression(), width in 0..60usize) {
+            // given
+            let options = PrettyOptions { width, ..PrettyOptions::default() };
+
+            // when
+            let printed = pprint_str(&expr, &options);
+
+            // then
+            let read_back = Reader::from_string(&printed)
+                .read()
+                .map_err(|error| TestCaseError::fail(error.to_string()))?;
+            prop_assert_eq!(expr, read_back, "printed as {}", printed);
+        }
+    }
+}
@@ -1,16 +1,49 @@
+use crate::collections::{Map, Set};
+use crate::eval::{Lambda, Scope};
+use crate::number::decimal::Decimal;
+use crate::number::{normalize_bigint, normalize_ratio};
+use crate::printer::write_char;
 use crate::reader::Expression::*;
-use crate::tokenizer::{Token, Tokenizer, ValueType};
+use crate::reader::ReaderError::{
+    DuplicateMapKey, DuplicateSetElement, InvalidMetadata, InvalidMetadataTarget, InvalidNumber,
+    InvalidTaggedLiteral, MismatchedDelimiter, MissingForm, OddMapForms,
+    UnexpectedClosingDelimiter, UnexpectedEndOfInput, UnknownTag,
+};
+use crate::span::{Span, SpanTree, Spanned};
+use crate::tokenizer::{Token, Tokenizer, TokenizerError, Unclosed, ValueType};
 use failure::Error;
+use num_bigint::BigInt;
+use num_rational::BigRational;
 use std::any::Any;
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::fmt::{Debug, Formatter};
+use std::hash::{Hash, Hasher};
+use std::io::BufRead;
+use std::iter::once;
+use std::rc::Rc;
 use std::string::String as StdString;
 
+pub type NativeFn = fn(&[Expression]) -> Result<Expression, Error>;
+
+/// Native function that also reads names from the scope it was registered in,
+/// like `*print-length*`.
+pub type ScopedFn = fn(&Scope, &[Expression]) -> Result<Expression, Error>;
+
+/// Turns the form following `#tag` into the value the tagged literal stands for.
+pub type TagHandler = fn(Expression) -> Result<Expression, ReaderError>;
+
+/// Handles tagged literals whose tag has no handler of its own, gets the tag
+/// without the `#`.
+pub type DefaultTagHandler = fn(&str, Expression) -> Result<Expression, ReaderError>;
+
 #[derive(Clone)]
 pub enum Function {
-    Native(fn(&[Expression]) -> Result<Expression, Error>),
-    Regular(Vec<Expression>),
+    Native(NativeFn),
+    Scoped(ScopedFn, Scope),
+    /// Function defined with `fn`, together with the scope it closes over.
+    Regular(Rc<Lambda>),
 }
 
 impl Debug for Function {
@@ -26,23 +59,163 @@ impl PartialEq<Function> for Function {
     }
 }
 
+/// Functions are all equal to each other, so they all hash the same.
+impl Hash for Function {
+    fn hash<H: Hasher>(&self, _: &mut H) {}
+}
+
 impl Function {
     pub fn call(&self, args: &[Expression]) -> Result<Expression, Error> {
         match self {
             Function::Native(f) => f(args),
-            Function::Regular(_exprs) => unimplemented!(),
+            Function::Scoped(f, scope) => f(scope, args),
+            Function::Regular(lambda) => Ok(lambda.call(args)?),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum Expression {
     Identifier(StdString),
+    /// `:name`, stored without the colon. Keywords evaluate to themselves.
+    Keyword(StdString),
+    Bool(bool),
+    Nil,
+    Char(char),
     String(StdString),
-    Integer(i32),
-    Float(f32),
+    Integer(i64),
+    BigInt(BigInt),
+    Ratio(BigRational),
+    Float(f64),
+    Decimal(Decimal),
     Fn(Function),
     List(Vec<Expression>),
+    Vector(Vec<Expression>),
+    Map(Map),
+    Set(Set),
+    /// Identifier or collection with metadata attached. Metadata does not take part
+    /// in equality, so the expression compares as the form it wraps.
+    WithMeta(Box<Expression>, Map),
+}
+
+impl Expression {
+    /// Metadata attached to the expression, if any.
+    pub fn meta(&self) -> Option<&Map> {
+        match self {
+            Expression::WithMeta(_, meta) => Some(meta),
+            _ => None,
+        }
+    }
+
+    /// Same expression with `meta` replacing its metadata. Empty metadata removes it.
+    pub fn with_meta(self, meta: Map) -> Expression {
+        let form = match self {
+            Expression::WithMeta(form, _) => *form,
+            form => form,
+        };
+        if meta.is_empty() {
+            form
+        } else {
+            Expression::WithMeta(Box::new(form), meta)
+        }
+    }
+
+    /// Expression without its metadata.
+    pub fn without_meta(&self) -> &Expression {
+        match self {
+            Expression::WithMeta(form, _) => form,
+            form => form,
+        }
+    }
+
+    /// Whether metadata can be attached: only identifiers and collections take it.
+    pub fn can_have_meta(&self) -> bool {
+        matches!(
+            self.without_meta(),
+            Expression::Identifier(_)
+                | Expression::List(_)
+                | Expression::Vector(_)
+                | Expression::Map(_)
+                | Expression::Set(_)
+        )
+    }
+
+    /// Name of the expression type used in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Expression::Identifier(_) => "identifier",
+            Expression::Keyword(_) => "keyword",
+            Expression::Bool(_) => "boolean",
+            Expression::Nil => "nil",
+            Expression::Char(_) => "char",
+            Expression::String(_) => "string",
+            Expression::Integer(_) | Expression::BigInt(_) => "integer",
+            Expression::Ratio(_) => "ratio",
+            Expression::Float(_) => "float",
+            Expression::Decimal(_) => "decimal",
+            Expression::Fn(_) => "function",
+            Expression::List(_) => "list",
+            Expression::Vector(_) => "vector",
+            Expression::Map(_) => "map",
+            Expression::Set(_) => "set",
+            Expression::WithMeta(form, _) => form.type_name(),
+        }
+    }
+}
+
+impl PartialEq for Expression {
+    fn eq(&self, other: &Expression) -> bool {
+        use Expression as E;
+        match (self.without_meta(), other.without_meta()) {
+            (E::Identifier(a), E::Identifier(b)) => a == b,
+            (E::Keyword(a), E::Keyword(b)) => a == b,
+            (E::Bool(a), E::Bool(b)) => a == b,
+            (E::Nil, E::Nil) => true,
+            (E::Char(a), E::Char(b)) => a == b,
+            (E::String(a), E::String(b)) => a == b,
+            (E::Integer(a), E::Integer(b)) => a == b,
+            (E::BigInt(a), E::BigInt(b)) => a == b,
+            (E::Ratio(a), E::Ratio(b)) => a == b,
+            // NaN equals itself here, or it could be added to a set again and again
+            (E::Float(a), E::Float(b)) => a == b || a.is_nan() && b.is_nan(),
+            (E::Decimal(a), E::Decimal(b)) => a == b,
+            (E::Fn(a), E::Fn(b)) => a == b,
+            (E::List(a), E::List(b)) => a == b,
+            (E::Vector(a), E::Vector(b)) => a == b,
+            (E::Map(a), E::Map(b)) => a == b,
+            (E::Set(a), E::Set(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Hash for Expression {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        if let Expression::WithMeta(form, _) = self {
+            return form.hash(state);
+        }
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Expression::Identifier(value)
+            | Expression::Keyword(value)
+            | Expression::String(value) => value.hash(state),
+            Expression::Bool(value) => value.hash(state),
+            Expression::Nil => {}
+            Expression::Char(value) => value.hash(state),
+            Expression::Integer(value) => value.hash(state),
+            Expression::BigInt(value) => value.hash(state),
+            Expression::Ratio(value) => value.hash(state),
+            // 0.0 equals -0.0 and NaNs equal each other, so they must hash the same
+            Expression::Float(value) if value.is_nan() => f64::NAN.to_bits().hash(state),
+            Expression::Float(value) => (value + 0.0).to_bits().hash(state),
+            Expression::Decimal(value) => value.hash(state),
+            Expression::Fn(function) => function.hash(state),
+            Expression::List(values) | Expression::Vector(values) => values.hash(state),
+            Expression::Map(map) => map.hash(state),
+            Expression::Set(set) => set.hash(state),
+            Expression::WithMeta(..) => unreachable!("metadata is skipped above"),
+        }
+    }
 }
 
 impl Display for Expression {
@@ -50,75 +223,546 @@ impl Display for Expression {
         match self {
             Expression::Float(value) => f.write_fmt(format_args!("{}", value))?,
             Expression::Integer(value) => f.write_fmt(format_args!("{}", value))?,
+            Expression::BigInt(value) => f.write_fmt(format_args!("{}", value))?,
+            Expression::Ratio(value) => f.write_fmt(format_args!("{}", value))?,
+            Expression::Decimal(value) => f.write_fmt(format_args!("{}M", value))?,
             Expression::Fn(_) => f.write_str("<function>")?,
             Expression::Identifier(value) => f.write_fmt(format_args!("{}", value))?,
+            Expression::Keyword(name) => f.write_fmt(format_args!(":{}", name))?,
+            Expression::Bool(value) => f.write_fmt(format_args!("{}", value))?,
+            Expression::Nil => f.write_str("nil")?,
+            Expression::Char(c) => write_char(f, *c)?,
             Expression::String(value) => f.write_fmt(format_args!("{}", value))?,
-            Expression::List(values) => {
-                f.write_str("(")?;
-                values
-                    .iter()
-                    .map(|s| Display::fmt(s, f))
-                    .collect::<Result<Vec<_>, _>>()?;
-                f.write_str(")")?;
-            }
+            Expression::List(values) => write_sequence(f, "(", values.iter(), ")")?,
+            Expression::Vector(values) => write_sequence(f, "[", values.iter(), "]")?,
+            Expression::Map(map) => write_sequence(
+                f,
+                "{",
+                map.iter().flat_map(|(k, v)| once(k).chain(once(v))),
+                "}",
+            )?,
+            Expression::Set(set) => write_sequence(f, "#{", set.iter(), "}")?,
+            Expression::WithMeta(form, _) => Display::fmt(form, f)?,
         }
         Ok(())
     }
 }
 
+fn write_sequence<'a>(
+    f: &mut Formatter,
+    open: &str,
+    values: impl Iterator<Item = &'a Expression>,
+    close: &str,
+) -> Result<(), std::fmt::Error> {
+    f.write_str(open)?;
+    for (index, value) in values.enumerate() {
+        if index > 0 {
+            f.write_str(" ")?;
+        }
+        Display::fmt(value, f)?;
+    }
+    f.write_str(close)
+}
+
+#[derive(Debug, Fail)]
+pub enum ReaderError {
+    #[fail(display = "{}: Missing form after {}", _1, _0)]
+    MissingForm(&'static str, Span),
+
+    #[fail(display = "{}: Unexpected '{}' outside of any collection", _1, _0)]
+    UnexpectedClosingDelimiter(char, Span),
+
+    #[fail(display = "{}: Expected '{}' but found '{}'", _2, _0, _1)]
+    MismatchedDelimiter(char, char, Span),
+
+    #[fail(display = "{}: Unexpected end of input, unclosed {}", _1, _0)]
+    UnexpectedEndOfInput(Unclosed, Span),
+
+    #[fail(display = "{}: Invalid number {}: {}", _2, _0, _1)]
+    InvalidNumber(StdString, StdString, Span),
+
+    #[fail(display = "{}: Map literal must contain an even number of forms", _0)]
+    OddMapForms(Span),
+
+    #[fail(display = "{}: Duplicate key in map literal", _0)]
+    DuplicateMapKey(Span),
+
+    #[fail(display = "{}: Duplicate element in set literal", _0)]
+    DuplicateSetElement(Span),
+
+    #[fail(
+        display = "{}: Metadata must be a map, keyword, identifier or string, not {}",
+        _1, _0
+    )]
+    InvalidMetadata(&'static str, Span),
+
+    #[fail(
+        display = "{}: Metadata can only be attached to identifiers and collections, not {}",
+        _1, _0
+    )]
+    InvalidMetadataTarget(&'static str, Span),
+
+    #[fail(display = "{}: No handler for tagged literal #{}", _1, _0)]
+    UnknownTag(StdString, Span),
+
+    /// Error returned by tag handlers, the reader adds the tag and span to it.
+    #[fail(display = "{}", _0)]
+    InvalidValue(StdString),
+
+    #[fail(display = "{}: Invalid #{} literal: {}", _2, _0, _1)]
+    InvalidTaggedLiteral(StdString, Box<ReaderError>, Span),
+}
+
+impl ReaderError {
+    /// Whether more input could turn the erroneous form into a valid one.
+    pub fn is_incomplete(&self) -> bool {
+        matches!(self, UnexpectedEndOfInput(..))
+    }
+}
+
+/// Whether reading failed only because the input ended in the middle of a form,
+/// either inside a collection or inside a single token.
+pub fn is_incomplete(error: &Error) -> bool {
+    match error.downcast_ref::<ReaderError>() {
+        Some(error) => error.is_incomplete(),
+        None => error
+            .downcast_ref::<TokenizerError>()
+            .is_some_and(TokenizerError::is_incomplete),
+    }
+}
+
+/// Handlers of tagged literals, shared by the `Reader` and the `Cst`.
+#[derive(Clone, Default)]
+pub struct TagRegistry {
+    handlers: HashMap<StdString, TagHandler>,
+    default_handler: Option<DefaultTagHandler>,
+}
+
+impl TagRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads `#tag form` with `handler`. The tag is given without the `#`.
+    pub fn with_tag(mut self, tag: &str, handler: TagHandler) -> Self {
+        self.handlers.insert(tag.to_owned(), handler);
+        self
+    }
+
+    /// Reads tagged literals whose tag has no handler with `handler`, instead of
+    /// failing with `UnknownTag`.
+    pub fn with_default_handler(mut self, handler: DefaultTagHandler) -> Self {
+        self.default_handler = Some(handler);
+        self
+    }
+
+    /// Value of tagged literal `#tag form` spanning `span`, as given by the handler
+    /// of the tag.
+    pub(crate) fn tagged_value(
+        &self,
+        tag: &str,
+        form: Expression,
+        span: Span,
+    ) -> Result<(Expression, SpanTree), Error> {
+        let value = match (self.handlers.get(tag), self.default_handler) {
+            (Some(handler), _) => handler(form),
+            (None, Some(handler)) => handler(tag, form),
+            (None, None) => return Err(UnknownTag(tag.to_owned(), span).into()),
+        };
+        match value {
+            Ok(value) => Ok((value, SpanTree::leaf(span))),
+            Err(error) => Err(InvalidTaggedLiteral(tag.to_owned(), Box::new(error), span).into()),
+        }
+    }
+}
+
 pub struct Reader {
     tokenizer: RefCell<Tokenizer>,
+    failed: bool,
+    tags: TagRegistry,
 }
 
 impl Reader {
     pub fn from_string(code: &str) -> Self {
+        Self::from_tokenizer(Tokenizer::from_string(code))
+    }
+
+    /// Reader pulling the input as forms are read, so that forms can be read from
+    /// a pipe as they arrive, or from inputs too large to fit in memory.
+    pub fn from_reader(input: impl BufRead + 'static) -> Self {
+        Self::from_tokenizer(Tokenizer::from_reader(input))
+    }
+
+    fn from_tokenizer(tokenizer: Tokenizer) -> Self {
         Self {
-            tokenizer: RefCell::new(Tokenizer::from_string(code)),
+            tokenizer: RefCell::new(tokenizer),
+            failed: false,
+            tags: TagRegistry::new(),
         }
     }
 
+    /// Sets the file name reported in spans of forms read from now on.
+    pub fn with_file(self, file: &str) -> Self {
+        self.tokenizer.borrow_mut().set_file(file);
+        self
+    }
+
+    /// Reads tagged literals with the handlers in `tags`, replacing the handlers
+    /// set so far.
+    pub fn with_tags(mut self, tags: TagRegistry) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Reads `#tag form` with `handler`. The tag is given without the `#`.
+    pub fn with_tag(mut self, tag: &str, handler: TagHandler) -> Self {
+        self.tags = self.tags.with_tag(tag, handler);
+        self
+    }
+
+    /// Reads tagged literals whose tag has no handler with `handler`, instead of
+    /// failing with `UnknownTag`.
+    pub fn with_default_tag_handler(mut self, handler: DefaultTagHandler) -> Self {
+        self.tags = self.tags.with_default_handler(handler);
+        self
+    }
+
     pub fn read(&self) -> Result<Expression, Error> {
-        let token = self.tokenizer.borrow_mut().next()?;
+        self.read_with_spans().map(|(expr, _)| expr)
+    }
+
+    /// Reads next form along with the spans of the form and all its sub-forms.
+    pub fn read_with_spans(&self) -> Result<(Expression, SpanTree), Error> {
+        let token = self.next_token()?;
         self.read_form(token)
     }
 
-    fn read_form(&self, token: Token) -> Result<Expression, Error> {
-        Ok(match token {
-            Token::Identifier(ident) => Expression::Identifier(ident),
-            Token::Value(value, ValueType::String) => Expression::String(value),
-            Token::Value(value, ValueType::Number) => self.read_number(&value)?,
-            Token::LeftParen => self.read_list()?,
-            _ => Expression::Identifier("--".to_owned()), // todo error
-        })
+    /// Reads next form, returns `None` when only whitespace and comments are left.
+    pub fn read_next(&self) -> Option<Result<Expression, Error>> {
+        match self.read() {
+            Err(error) if is_end_of_input(&error) => None,
+            result => Some(result),
+        }
     }
 
-    fn read_number(&self, value: &str) -> Result<Expression, Error> {
-        Ok(if value.contains('.') {
-            let val = value.parse::<f32>()?;
-            Expression::Float(val)
-        } else {
-            let val = value.parse::<i32>()?;
-            Expression::Integer(val)
+    /// Reads all remaining top-level forms.
+    pub fn read_all(&self) -> Result<Vec<Expression>, Error> {
+        std::iter::from_fn(|| self.read_next()).collect()
+    }
+
+    /// Next token that is not part of a datum comment.
+    fn next_token(&self) -> Result<Spanned<Token>, Error> {
+        loop {
+            let token = self.tokenizer.borrow_mut().next_spanned()?;
+            if token.node != Token::DatumComment {
+                return Ok(token);
+            }
+            let discarded = self.next_token_inside(Unclosed::ReaderMacro("#_"), &token.span)?;
+            if discarded.node.is_closing() {
+                return Err(MissingForm("#_", token.span).into());
+            }
+            self.read_form(discarded)?;
+        }
+    }
+
+    /// Next token of a form opened at `span`. Running out of input there means the
+    /// input is incomplete rather than empty.
+    fn next_token_inside(&self, unclosed: Unclosed, span: &Span) -> Result<Spanned<Token>, Error> {
+        self.next_token()
+            .map_err(|error| match error.downcast_ref::<TokenizerError>() {
+                Some(TokenizerError::UnexpectedEndOfInput) => {
+                    UnexpectedEndOfInput(unclosed, span.clone()).into()
+                }
+                _ => error,
+            })
+    }
+
+    fn read_form(&self, token: Spanned<Token>) -> Result<(Expression, SpanTree), Error> {
+        let span = token.span;
+        Ok(match token.node {
+            Token::Identifier(ident) if is_tag(&ident) => self.read_tagged(&ident[1..], span)?,
+            token @ Token::Identifier(_) | token @ Token::Value(..) => {
+                (read_atom(token, &span)?, SpanTree::leaf(span))
+            }
+            Token::LeftParen => {
+                let (contents, tree) = self.read_sequence('(', span)?;
+                (List(contents), tree)
+            }
+            Token::LeftBracket => {
+                let (contents, tree) = self.read_sequence('[', span)?;
+                (Vector(contents), tree)
+            }
+            Token::LeftBrace => self.read_map(span)?,
+            Token::LeftSetBrace => self.read_set(span)?,
+            Token::Quote => self.read_quoted("quote", "'", span)?,
+            Token::Quasiquote => self.read_quoted("quasiquote", "`", span)?,
+            Token::Unquote => self.read_quoted("unquote", "~", span)?,
+            Token::UnquoteSplicing => self.read_quoted("unquote-splicing", "~@", span)?,
+            Token::Meta => self.read_with_meta(span)?,
+            Token::RightParen | Token::RightBracket | Token::RightBrace => {
+                return Err(UnexpectedClosingDelimiter(closing_char(&token.node), span).into())
+            }
+            Token::DatumComment => unreachable!("datum comments are skipped by next_token"),
         })
     }
 
-    fn read_list(&self) -> Result<Expression, Error> {
+    /// Reads form following a quoting reader macro and wraps it into `(symbol form)`.
+    fn read_quoted(
+        &self,
+        symbol: &str,
+        text: &'static str,
+        span: Span,
+    ) -> Result<(Expression, SpanTree), Error> {
+        let (form, form_span) = self.read_macro_form(text, &span)?;
+        let tree = SpanTree {
+            span: span.to(&form_span.span),
+            children: vec![SpanTree::leaf(span), form_span],
+        };
+        Ok((List(vec![Identifier(symbol.to_owned()), form]), tree))
+    }
+
+    /// Reads `^metadata form` and attaches the metadata to the form, merging it
+    /// over metadata the form already has.
+    fn read_with_meta(&self, span: Span) -> Result<(Expression, SpanTree), Error> {
+        let (meta, meta_span) = self.read_macro_form("^", &span)?;
+        let meta = meta_map(meta, meta_span.span)?;
+        let (form, form_span) = self.read_macro_form("^", &span)?;
+        attach_meta(meta, form, form_span, &span)
+    }
+
+    /// Reads form that the reader macro `text` found at `span` applies to.
+    fn read_macro_form(
+        &self,
+        text: &'static str,
+        span: &Span,
+    ) -> Result<(Expression, SpanTree), Error> {
+        let token = self.next_token_inside(Unclosed::ReaderMacro(text), span)?;
+        if token.node.is_closing() {
+            return Err(MissingForm(text, span.clone()).into());
+        }
+        self.read_form(token)
+    }
+
+    /// Reads form following `#tag` and passes it to the handler of the tag.
+    fn read_tagged(&self, tag: &str, span: Span) -> Result<(Expression, SpanTree), Error> {
+        let (form, form_span) = self.read_macro_form("tagged literal", &span)?;
+        self.tags.tagged_value(tag, form, span.to(&form_span.span))
+    }
+
+    /// Reads forms up to the delimiter closing the `opening` one found at `span`.
+    fn read_sequence(
+        &self,
+        opening: char,
+        span: Span,
+    ) -> Result<(Vec<Expression>, SpanTree), Error> {
+        let closing = match opening {
+            '[' => ']',
+            '{' | '#' => '}',
+            _ => ')',
+        };
         let mut contents: Vec<Expression> = vec![];
+        let mut children: Vec<SpanTree> = vec![];
         loop {
-            let token = self.tokenizer.borrow_mut().next()?;
-            match token {
-                Token::RightParen => break,
-                other_token => {
-                    let expr = self.read_form(other_token)?;
-                    contents.push(expr);
+            let token = self.next_token_inside(Unclosed::Collection(opening), &span)?;
+            if token.node.is_closing() {
+                let found = closing_char(&token.node);
+                if found != closing {
+                    return Err(MismatchedDelimiter(closing, found, token.span).into());
                 }
+                let tree = SpanTree {
+                    span: span.to(&token.span),
+                    children,
+                };
+                return Ok((contents, tree));
             }
+            let (expr, child) = self.read_form(token)?;
+            contents.push(expr);
+            children.push(child);
+        }
+    }
+
+    fn read_map(&self, span: Span) -> Result<(Expression, SpanTree), Error> {
+        let (contents, tree) = self.read_sequence('{', span)?;
+        collect_map(contents, tree)
+    }
+
+    fn read_set(&self, span: Span) -> Result<(Expression, SpanTree), Error> {
+        let (contents, tree) = self.read_sequence('#', span)?;
+        collect_set(contents, tree)
+    }
+}
+
+/// Expression of an identifier or value token.
+pub(crate) fn read_atom(token: Token, span: &Span) -> Result<Expression, Error> {
+    Ok(match token {
+        Token::Identifier(ident) => read_symbol(ident),
+        Token::Value(value, ValueType::String) => Expression::String(value),
+        Token::Value(value, ValueType::Char) => Expression::Char(
+            value
+                .chars()
+                .next()
+                .expect("character literal is never empty"),
+        ),
+        Token::Value(value, ValueType::Number) => read_number(&value)
+            .map_err(|error| InvalidNumber(value.clone(), error.to_string(), span.clone()))?,
+        token => unreachable!("{:?} is not an atom", token),
+    })
+}
+
+/// Map of the forms read between `{` and `}`, `tree` holding their spans.
+pub(crate) fn collect_map(
+    contents: Vec<Expression>,
+    tree: SpanTree,
+) -> Result<(Expression, SpanTree), Error> {
+    if !contents.len().is_multiple_of(2) {
+        return Err(OddMapForms(tree.span).into());
+    }
+    let mut map = Map::new();
+    let mut contents = contents.into_iter();
+    let mut key_spans = tree.children.iter().step_by(2);
+    while let (Some(key), Some(value)) = (contents.next(), contents.next()) {
+        let key_span = key_spans.next().map(|key| key.span.clone());
+        if map.insert(key, value).is_some() {
+            return Err(DuplicateMapKey(key_span.unwrap_or_default()).into());
+        }
+    }
+    Ok((Map(map), tree))
+}
+
+/// Set of the forms read between `#{` and `}`, `tree` holding their spans.
+pub(crate) fn collect_set(
+    contents: Vec<Expression>,
+    tree: SpanTree,
+) -> Result<(Expression, SpanTree), Error> {
+    let mut set = Set::new();
+    for (element, element_tree) in contents.into_iter().zip(&tree.children) {
+        if !set.insert(element) {
+            return Err(DuplicateSetElement(element_tree.span.clone()).into());
+        }
+    }
+    Ok((Set(set), tree))
+}
+
+/// Metadata map given by the form following `^`: a map, or a keyword or tag
+/// standing for one.
+pub(crate) fn meta_map(meta: Expression, span: Span) -> Result<Map, Error> {
+    Ok(match meta {
+        Map(map) => map,
+        Keyword(_) => once((meta, Bool(true))).collect(),
+        Identifier(_) | String(_) => once((Keyword("tag".to_owned()), meta)).collect(),
+        other => return Err(InvalidMetadata(other.type_name(), span).into()),
+    })
+}
+
+/// Attaches `meta` read after `^` found at `span` to `form`, merging it over
+/// metadata the form already has.
+pub(crate) fn attach_meta(
+    meta: Map,
+    form: Expression,
+    form_span: SpanTree,
+    span: &Span,
+) -> Result<(Expression, SpanTree), Error> {
+    if !form.can_have_meta() {
+        return Err(InvalidMetadataTarget(form.type_name(), form_span.span).into());
+    }
+    let mut merged = form.meta().cloned().unwrap_or_default();
+    for (key, value) in meta.iter() {
+        merged.insert(key.clone(), value.clone());
+    }
+    let tree = SpanTree {
+        span: span.to(&form_span.span),
+        children: form_span.children,
+    };
+    Ok((form.with_meta(merged), tree))
+}
+
+/// Parses number literal already validated by the tokenizer.
+fn read_number(value: &str) -> Result<Expression, Error> {
+    let digits = value.replace('_', "");
+    if let Some(decimal) = digits.strip_suffix('M') {
+        return Ok(Expression::Decimal(decimal.parse()?));
+    }
+    let (negative, unsigned) = match digits.as_bytes().first() {
+        Some(b'-') => (true, &digits[1..]),
+        Some(b'+') => (false, &digits[1..]),
+        _ => (false, &digits[..]),
+    };
+    let radix = match unsigned.get(..2) {
+        Some("0x") | Some("0X") => 16,
+        Some("0o") | Some("0O") => 8,
+        Some("0b") | Some("0B") => 2,
+        _ => 10,
+    };
+    if radix == 10 && unsigned.contains(&['.', 'e', 'E'][..]) {
+        let value = digits.parse::<f64>()?;
+        if !value.is_finite() {
+            bail!("out of range of a float");
+        }
+        return Ok(Expression::Float(value));
+    }
+    if let Some(slash) = digits.find('/') {
+        let numerator: BigInt = digits[..slash].parse()?;
+        let denominator: BigInt = digits[slash + 1..].parse()?;
+        return Ok(normalize_ratio(BigRational::new(numerator, denominator)));
+    }
+    let magnitude = if radix == 10 {
+        unsigned
+    } else {
+        &unsigned[2..]
+    };
+    let val = BigInt::parse_bytes(magnitude.as_bytes(), radix)
+        .ok_or_else(|| format_err!("Invalid integer literal: {}", value))?;
+    Ok(normalize_bigint(if negative { -val } else { val }))
+}
+
+/// Whether identifier is a tag of a tagged literal, like `#inst`.
+pub(crate) fn is_tag(ident: &str) -> bool {
+    let mut chars = ident.chars();
+    chars.next() == Some('#') && chars.next().is_some_and(char::is_alphabetic)
+}
+
+/// Literal named by a symbol, like `true` or `:keyword`, or an identifier.
+fn read_symbol(name: StdString) -> Expression {
+    match name.as_str() {
+        "true" => Bool(true),
+        "false" => Bool(false),
+        "nil" => Nil,
+        _ if name.len() > 1 && name.starts_with(':') => Keyword(name[1..].to_owned()),
+        _ => Identifier(name),
+    }
+}
+
+fn is_end_of_input(error: &Error) -> bool {
+    matches!(
+        error.downcast_ref::<TokenizerError>(),
+        Some(TokenizerError::UnexpectedEndOfInput)
+    )
+}
+
+/// Yields top-level forms until the end of input, stopping after the first error.
+impl Iterator for Reader {
+    type Item = Result<Expression, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
         }
-        Ok(List(contents))
+        let result = self.read_next();
+        self.failed = matches!(result, Some(Err(_)));
+        result
+    }
+}
+
+fn closing_char(token: &Token) -> char {
+    match token {
+        Token::RightBracket => ']',
+        Token::RightBrace => '}',
+        _ => ')',
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::approx_constant)]
 mod test {
     use super::*;
 
@@ -169,7 +813,191 @@ mod test {
             Ok(())
         }
 
-        // TODO: test for reading invalid number like '3.2xc'
+        #[test]
+        fn should_read_number_literal_variants() -> Result<(), Error> {
+            for (code, expected) in &[
+                ("-5", Integer(-5)),
+                ("+3", Integer(3)),
+                ("0xFF", Integer(255)),
+                ("-0x10", Integer(-16)),
+                ("0b1010", Integer(10)),
+                ("0o17", Integer(15)),
+                ("1_000_000", Integer(1_000_000)),
+                (".5", Float(0.5)),
+                ("-2.5", Float(-2.5)),
+                ("1e3", Float(1000.0)),
+                ("2.5E-1", Float(0.25)),
+                ("6/3", Integer(2)),
+            ] {
+                // given
+                let reader = Reader::from_string(code);
+
+                // expect
+                assert_eq!(*expected, reader.read()?, "reading {}", code);
+            }
+            Ok(())
+        }
+
+        #[test]
+        fn should_read_integers_beyond_i64_as_big_integers() -> Result<(), Error> {
+            // given
+            let reader = Reader::from_string("(9223372036854775807 -0x1_0000_0000_0000_0000)");
+
+            // expect
+            assert_eq!(
+                List(vec![
+                    Integer(i64::MAX),
+                    BigInt(-(num_bigint::BigInt::from(1) << 64usize)),
+                ]),
+                reader.read()?
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn should_read_decimals_keeping_scale() -> Result<(), Error> {
+            // given
+            let reader = Reader::from_string("(12.50M -0.05M 7M .5M)");
+
+            // when
+            let expr = reader.read()?;
+
+            // then
+            assert_eq!("(12.50M -0.05M 7M 0.5M)", expr.to_string());
+            Ok(())
+        }
+
+        #[test]
+        fn should_read_normalized_ratios() -> Result<(), Error> {
+            // given
+            let reader = Reader::from_string("-2/6");
+
+            // when
+            let expr = reader.read()?;
+
+            // then
+            assert_eq!(Ratio(BigRational::new((-1).into(), 3.into())), expr);
+            assert_eq!("-1/3", expr.to_string());
+            Ok(())
+        }
+
+        #[test]
+        fn should_fail_reading_invalid_number() {
+            // given
+            let reader = Reader::from_string("(+ 3.2xc 1)");
+
+            // when
+            let error = reader.read().err().unwrap();
+
+            // then
+            match error.downcast::<TokenizerError>() {
+                Ok(TokenizerError::InvalidNumber(text, _, span)) => {
+                    assert_eq!("3.2xc", text);
+                    assert_eq!((3, 8), (span.start, span.end));
+                }
+                other => panic!("Expected invalid number error, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn should_fail_reading_float_out_of_range() {
+            // given
+            let reader = Reader::from_string("[1 1e400]");
+
+            // when
+            let error = reader.read().err().unwrap();
+
+            // then
+            match error.downcast::<ReaderError>() {
+                Ok(InvalidNumber(text, _, span)) => {
+                    assert_eq!("1e400", text);
+                    assert_eq!((3, 8), (span.start, span.end));
+                }
+                other => panic!("Expected invalid number error, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn should_read_booleans_nil_and_keywords() -> Result<(), Error> {
+            // given
+            let reader = Reader::from_string("(true false nil :key : truer)");
+
+            // expect
+            assert_eq!(
+                List(vec![
+                    Bool(true),
+                    Bool(false),
+                    Nil,
+                    Keyword("key".to_owned()),
+                    Identifier(":".to_owned()),
+                    Identifier("truer".to_owned()),
+                ]),
+                reader.read()?
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn should_display_booleans_nil_and_keywords() -> Result<(), Error> {
+            // given
+            let reader = Reader::from_string("{:a true :b nil}");
+
+            // expect
+            assert_eq!("{:a true :b nil}", reader.read()?.to_string());
+            Ok(())
+        }
+
+        #[test]
+        fn should_read_character_literals() -> Result<(), Error> {
+            // given
+            let reader = Reader::from_string("[\\a \\( \\é \\space \\newline \\u00E9 \\u]");
+
+            // expect
+            assert_eq!(
+                Vector(vec![
+                    Char('a'),
+                    Char('('),
+                    Char('é'),
+                    Char(' '),
+                    Char('\n'),
+                    Char('é'),
+                    Char('u'),
+                ]),
+                reader.read()?
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn should_fail_reading_unknown_character_name() {
+            // given
+            let reader = Reader::from_string("(\\spaces)");
+
+            // when
+            let error = reader.read().err().unwrap();
+
+            // then
+            match error.downcast::<TokenizerError>() {
+                Ok(TokenizerError::InvalidCharacter(text, _, span)) => {
+                    assert_eq!("spaces", text);
+                    assert_eq!((1, 8), (span.start, span.end));
+                }
+                other => panic!("Expected invalid character error, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn should_display_characters_readably() -> Result<(), Error> {
+            // given
+            let reader = Reader::from_string("[\\a \\é \\space \\tab \\u0007 \\) \\\\]");
+
+            // expect
+            assert_eq!(
+                "[\\a \\é \\space \\tab \\u0007 \\) \\\\]",
+                reader.read()?.to_string()
+            );
+            Ok(())
+        }
 
         #[test]
         fn should_rad_list() -> Result<(), Error> {
@@ -209,4 +1037,779 @@ mod test {
         );
         Ok(())
     }
+
+    mod spans {
+        use super::*;
+
+        #[test]
+        fn should_read_spans_of_nested_forms() -> Result<(), Error> {
+            // given
+            let code = "(say-hello\n  (str \"John\"))";
+            let reader = Reader::from_string(code).with_file("hello.rp");
+
+            // when
+            let (_, spans) = reader.read_with_spans()?;
+
+            // then
+            assert_eq!((0, 26), (spans.span.start, spans.span.end));
+            assert_eq!(2, spans.children.len());
+            let inner = &spans.children[1];
+            assert_eq!("hello.rp:2:3", inner.span.to_string());
+            assert_eq!((13, 25), (inner.span.start, inner.span.end));
+            let string = &inner.children[1].span;
+            assert_eq!((18, 24), (string.start, string.end));
+            Ok(())
+        }
+
+        #[test]
+        fn should_compare_forms_regardless_of_position() -> Result<(), Error> {
+            // given
+            let first = Reader::from_string("(a b)").read_with_spans()?;
+            let second = Reader::from_string("  ( a\n b )").read_with_spans()?;
+
+            // expect
+            assert_eq!(first.0, second.0);
+            assert_ne!(first.1, second.1);
+            Ok(())
+        }
+    }
+
+    mod comments {
+        use super::*;
+
+        #[test]
+        fn should_ignore_comments_inside_lists() -> Result<(), Error> {
+            // given
+            let code = "(a ; first\n #| second |# b)";
+            let reader = Reader::from_string(code);
+
+            // expect
+            assert_eq!(
+                List(vec![Identifier("a".to_owned()), Identifier("b".to_owned())]),
+                reader.read()?
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn should_discard_form_after_datum_comment() -> Result<(), Error> {
+            // given
+            let code = "(a #_(b \"c\" (d)) e)";
+            let reader = Reader::from_string(code);
+
+            // expect
+            assert_eq!(
+                List(vec![Identifier("a".to_owned()), Identifier("e".to_owned())]),
+                reader.read()?
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn should_discard_stacked_datum_comments() -> Result<(), Error> {
+            // given
+            let reader = Reader::from_string("#_ #_ a b \"c\"");
+
+            // expect
+            assert_eq!(String("c".to_owned()), reader.read()?);
+            Ok(())
+        }
+
+        #[test]
+        fn should_read_strings_adjacent_to_comments() -> Result<(), Error> {
+            // given
+            let code = "(\"a\";comment\n\"b\"#|block|#\"c\"#_\"d\")";
+            let reader = Reader::from_string(code);
+
+            // expect
+            assert_eq!(
+                List(vec![
+                    String("a".to_owned()),
+                    String("b".to_owned()),
+                    String("c".to_owned()),
+                ]),
+                reader.read()?
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn should_read_form_followed_by_comment_at_end_of_input() -> Result<(), Error> {
+            // given
+            let reader = Reader::from_string("(a) ; done");
+
+            // expect
+            assert_eq!(List(vec![Identifier("a".to_owned())]), reader.read()?);
+            assert!(reader.read().is_err());
+            Ok(())
+        }
+
+        #[test]
+        fn should_fail_when_datum_comment_has_nothing_to_discard() {
+            // given
+            let reader = Reader::from_string("(a #_)");
+
+            // when
+            let error = reader.read().err().unwrap();
+
+            // then
+            match error.downcast::<ReaderError>() {
+                Ok(MissingForm("#_", span)) => assert_eq!(3, span.start),
+                other => panic!("Expected missing discarded form error, got {:?}", other),
+            }
+        }
+    }
+
+    mod quoting {
+        use super::*;
+
+        fn quoted(symbol: &str, form: Expression) -> Expression {
+            List(vec![Identifier(symbol.to_owned()), form])
+        }
+
+        #[test]
+        fn should_expand_quoting_reader_macros() -> Result<(), Error> {
+            for (code, symbol) in &[
+                ("'x", "quote"),
+                ("`x", "quasiquote"),
+                ("~x", "unquote"),
+                ("~@x", "unquote-splicing"),
+            ] {
+                // given
+                let reader = Reader::from_string(code);
+
+                // expect
+                assert_eq!(quoted(symbol, Identifier("x".to_owned())), reader.read()?);
+            }
+            Ok(())
+        }
+
+        #[test]
+        fn should_expand_nested_quoting_reader_macros() -> Result<(), Error> {
+            // given
+            let reader = Reader::from_string("`(a ~b ~@'(c))");
+
+            // expect
+            assert_eq!(
+                quoted(
+                    "quasiquote",
+                    List(vec![
+                        Identifier("a".to_owned()),
+                        quoted("unquote", Identifier("b".to_owned())),
+                        quoted(
+                            "unquote-splicing",
+                            quoted("quote", List(vec![Identifier("c".to_owned())]))
+                        ),
+                    ])
+                ),
+                reader.read()?
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn should_span_quoted_form_from_reader_macro() -> Result<(), Error> {
+            // given
+            let reader = Reader::from_string(" '(a b)");
+
+            // when
+            let (_, spans) = reader.read_with_spans()?;
+
+            // then
+            assert_eq!((1, 7), (spans.span.start, spans.span.end));
+            let quote = &spans.children[0].span;
+            assert_eq!((1, 2), (quote.start, quote.end));
+            Ok(())
+        }
+
+        #[test]
+        fn should_fail_when_quote_has_nothing_to_quote() {
+            // given
+            let reader = Reader::from_string("(a ')");
+
+            // when
+            let error = reader.read().err().unwrap();
+
+            // then
+            match error.downcast::<ReaderError>() {
+                Ok(MissingForm("'", span)) => assert_eq!(3, span.start),
+                other => panic!("Expected missing form error, got {:?}", other),
+            }
+        }
+    }
+
+    mod collections {
+        use super::*;
+
+        #[test]
+        fn should_read_vector() -> Result<(), Error> {
+            // given
+            let reader = Reader::from_string("[1 \"two\" [three]]");
+
+            // expect
+            assert_eq!(
+                Vector(vec![
+                    Integer(1),
+                    String("two".to_owned()),
+                    Vector(vec![Identifier("three".to_owned())]),
+                ]),
+                reader.read()?
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn should_read_map() -> Result<(), Error> {
+            // given
+            let reader = Reader::from_string("{a 1, b [2]}");
+
+            // when
+            let expr = reader.read()?;
+
+            // then
+            let expected: Map = vec![
+                (Identifier("a".to_owned()), Integer(1)),
+                (Identifier("b".to_owned()), Vector(vec![Integer(2)])),
+            ]
+            .into_iter()
+            .collect();
+            assert_eq!(Map(expected), expr);
+            Ok(())
+        }
+
+        #[test]
+        fn should_fail_on_mismatched_delimiter() {
+            // given
+            let reader = Reader::from_string("(a [b)]");
+
+            // when
+            let error = reader.read().err().unwrap();
+
+            // then
+            match error.downcast::<ReaderError>() {
+                Ok(MismatchedDelimiter(']', ')', span)) => assert_eq!(5, span.start),
+                other => panic!("Expected mismatched delimiter error, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn should_fail_on_unexpected_closing_delimiter() {
+            // given
+            let reader = Reader::from_string("(a) ]");
+
+            // when
+            reader.read().unwrap();
+            let error = reader.read().err().unwrap();
+
+            // then
+            match error.downcast::<ReaderError>() {
+                Ok(UnexpectedClosingDelimiter(']', span)) => assert_eq!("1:5", span.to_string()),
+                other => panic!(
+                    "Expected unexpected closing delimiter error, got {:?}",
+                    other
+                ),
+            }
+        }
+
+        #[test]
+        fn should_fail_on_odd_number_of_map_forms() {
+            // given
+            let reader = Reader::from_string("{a 1 b}");
+
+            // when
+            let error = reader.read().err().unwrap();
+
+            // then
+            match error.downcast::<ReaderError>() {
+                Ok(OddMapForms(span)) => assert_eq!((0, 7), (span.start, span.end)),
+                other => panic!("Expected odd map forms error, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn should_fail_on_duplicate_map_key() {
+            // given
+            let reader = Reader::from_string("{a 1 a 2}");
+
+            // when
+            let error = reader.read().err().unwrap();
+
+            // then
+            match error.downcast::<ReaderError>() {
+                Ok(DuplicateMapKey(span)) => assert_eq!(5, span.start),
+                other => panic!("Expected duplicate key error, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn should_read_set() -> Result<(), Error> {
+            // given
+            let reader = Reader::from_string("#{1 :a [2]}");
+
+            // when
+            let expr = reader.read()?;
+
+            // then
+            let expected: Set = vec![
+                Keyword("a".to_owned()),
+                Vector(vec![Integer(2)]),
+                Integer(1),
+            ]
+            .into_iter()
+            .collect();
+            assert_eq!(Set(expected), expr);
+            assert_eq!("#{1 :a [2]}", expr.to_string());
+            Ok(())
+        }
+
+        #[test]
+        fn should_fail_on_duplicate_set_element() {
+            // given
+            let reader = Reader::from_string("#{[1 2] 3 [1 2]}");
+
+            // when
+            let error = reader.read().err().unwrap();
+
+            // then
+            match error.downcast::<ReaderError>() {
+                Ok(DuplicateSetElement(span)) => assert_eq!((10, 15), (span.start, span.end)),
+                other => panic!("Expected duplicate set element error, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn should_hash_equal_expressions_equally() -> Result<(), Error> {
+            // given
+            let hash = |code: &str| -> Result<u64, Error> {
+                let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                Reader::from_string(code).read()?.hash(&mut hasher);
+                Ok(hasher.finish())
+            };
+
+            // expect
+            assert_eq!(hash("{:a 1 :b #{2 3}}")?, hash("{:b #{3 2} :a 1}")?);
+            assert_eq!(hash("1.50M")?, hash("1.5M")?);
+            assert_eq!(hash("0.0")?, hash("-0.0")?);
+            assert_ne!(hash("[1 2]")?, hash("[2 1]")?);
+            Ok(())
+        }
+
+        #[test]
+        fn should_display_collections() -> Result<(), Error> {
+            // given
+            let reader = Reader::from_string("([1 2] {a b})");
+
+            // expect
+            assert_eq!("([1 2] {a b})", reader.read()?.to_string());
+            Ok(())
+        }
+    }
+
+    mod metadata {
+        use super::*;
+
+        fn meta_of(code: &str) -> Result<Option<Map>, Error> {
+            Ok(Reader::from_string(code).read()?.meta().cloned())
+        }
+
+        fn map(code: &str) -> Result<Map, Error> {
+            match Reader::from_string(code).read()? {
+                Map(map) => Ok(map),
+                other => panic!("Expected map, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn should_attach_metadata_to_following_form() -> Result<(), Error> {
+            // expect
+            assert_eq!(
+                Some(map("{:doc \"x\" :private true}")?),
+                meta_of("^{:doc \"x\" :private true} a")?
+            );
+            assert_eq!(Some(map("{:dynamic true}")?), meta_of("^:dynamic *out*")?);
+            assert_eq!(Some(map("{:tag String}")?), meta_of("^String [a]")?);
+            assert_eq!(
+                Some(map("{:a 2 :b true}")?),
+                meta_of("^{:a 2} ^{:a 1 :b true} #{}")?
+            );
+            assert_eq!(None, meta_of("(a)")?);
+            Ok(())
+        }
+
+        #[test]
+        fn should_ignore_metadata_when_comparing() -> Result<(), Error> {
+            // given
+            let with_meta = Reader::from_string("(def ^:private x ^{:doc \"v\"} [1])").read()?;
+            let without_meta = Reader::from_string("(def x [1])").read()?;
+
+            // expect
+            assert_eq!(without_meta, with_meta);
+            assert_eq!("(def x [1])", with_meta.to_string());
+            Ok(())
+        }
+
+        #[test]
+        fn should_span_form_from_metadata() -> Result<(), Error> {
+            // given
+            let reader = Reader::from_string("(^:a [b c])");
+
+            // when
+            let (_, spans) = reader.read_with_spans()?;
+
+            // then
+            let vector = &spans.children[0];
+            assert_eq!((1, 10), (vector.span.start, vector.span.end));
+            assert_eq!(2, vector.children.len());
+            Ok(())
+        }
+
+        #[test]
+        fn should_fail_on_invalid_metadata() {
+            for (code, expected) in &[
+                (
+                    "^1 a",
+                    "1:2: Metadata must be a map, keyword, identifier or string, not integer",
+                ),
+                (
+                    "^:a \"text\"",
+                    "1:5: Metadata can only be attached to identifiers and collections, not string",
+                ),
+                ("(^:a)", "1:2: Missing form after ^"),
+            ] {
+                // when
+                let error = Reader::from_string(code).read().err().unwrap();
+
+                // then
+                assert_eq!(*expected, error.to_string());
+            }
+        }
+    }
+
+    mod tagged_literals {
+        use super::*;
+
+        fn upper(form: Expression) -> Result<Expression, ReaderError> {
+            match form {
+                String(text) => Ok(String(text.to_uppercase())),
+                other => Err(ReaderError::InvalidValue(format!(
+                    "expected a string, got {}",
+                    other.type_name()
+                ))),
+            }
+        }
+
+        #[test]
+        fn should_read_tagged_literal_with_registered_handler() -> Result<(), Error> {
+            // given
+            let reader =
+                Reader::from_string("[#upper \"abc\" #upper #_ 1 \"d\"]").with_tag("upper", upper);
+
+            // when
+            let (expr, spans) = reader.read_with_spans()?;
+
+            // then
+            assert_eq!(
+                Vector(vec![String("ABC".to_owned()), String("D".to_owned())]),
+                expr
+            );
+            let first = &spans.children[0].span;
+            assert_eq!((1, 13), (first.start, first.end));
+            Ok(())
+        }
+
+        #[test]
+        fn should_fail_on_unknown_tag() {
+            // given
+            let reader = Reader::from_string("(#inst \"2026-10-17\")");
+
+            // when
+            let error = reader.read().err().unwrap();
+
+            // then
+            match error.downcast::<ReaderError>() {
+                Ok(UnknownTag(tag, span)) => {
+                    assert_eq!("inst", tag);
+                    assert_eq!((1, 19), (span.start, span.end));
+                }
+                other => panic!("Expected unknown tag error, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn should_report_handler_errors_with_tag_and_position() {
+            // given
+            let reader = Reader::from_string("\n #upper 12").with_tag("upper", upper);
+
+            // when
+            let error = reader.read().err().unwrap();
+
+            // then
+            assert_eq!(
+                "2:2: Invalid #upper literal: expected a string, got integer",
+                error.to_string()
+            );
+        }
+
+        #[test]
+        fn should_use_default_handler_for_unknown_tags() -> Result<(), Error> {
+            // given
+            let reader = Reader::from_string("#uuid \"f81d\" #upper \"x\"")
+                .with_tag("upper", upper)
+                .with_default_tag_handler(|tag, form| {
+                    Ok(List(vec![Keyword(tag.to_owned()), form]))
+                });
+
+            // expect
+            assert_eq!(
+                List(vec![Keyword("uuid".to_owned()), String("f81d".to_owned())]),
+                reader.read()?
+            );
+            assert_eq!(String("X".to_owned()), reader.read()?);
+            Ok(())
+        }
+
+        #[test]
+        fn should_read_hash_identifiers_that_are_not_tags() -> Result<(), Error> {
+            // expect
+            assert_eq!(Identifier("#".to_owned()), Reader::from_string("#").read()?);
+            assert_eq!(
+                Identifier("#1".to_owned()),
+                Reader::from_string("#1").read()?
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn should_fail_when_tag_has_nothing_to_tag() {
+            // given
+            let reader = Reader::from_string("(#inst)");
+
+            // when
+            let error = reader.read().err().unwrap();
+
+            // then
+            match error.downcast::<ReaderError>() {
+                Ok(MissingForm(_, span)) => assert_eq!(1, span.start),
+                other => panic!("Expected missing form error, got {:?}", other),
+            }
+            assert!(is_incomplete(
+                &Reader::from_string("#inst").read().err().unwrap()
+            ));
+        }
+    }
+
+    mod top_level_forms {
+        use super::*;
+
+        #[test]
+        fn should_read_all_forms() -> Result<(), Error> {
+            // given
+            let reader = Reader::from_string("(def a 1) ; first\n a \"b\" #| done |#\n");
+
+            // when
+            let forms = reader.read_all()?;
+
+            // then
+            assert_eq!(
+                vec![
+                    List(vec![
+                        Identifier("def".to_owned()),
+                        Identifier("a".to_owned()),
+                        Integer(1),
+                    ]),
+                    Identifier("a".to_owned()),
+                    String("b".to_owned()),
+                ],
+                forms
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn should_read_nothing_from_blank_input() -> Result<(), Error> {
+            // expect
+            assert!(Reader::from_string("").read_all()?.is_empty());
+            assert!(Reader::from_string("  ; just a comment")
+                .read_all()?
+                .is_empty());
+            assert!(Reader::from_string("#_ skipped").read_all()?.is_empty());
+            Ok(())
+        }
+
+        #[test]
+        fn should_fail_reading_all_forms_of_truncated_input() {
+            // given
+            let reader = Reader::from_string("a (b");
+
+            // when
+            let error = reader.read_all().err().unwrap();
+
+            // then
+            assert!(is_incomplete(&error));
+        }
+
+        #[test]
+        fn should_iterate_until_end_of_input() {
+            // given
+            let reader = Reader::from_string("1 2");
+
+            // when
+            let forms: Vec<_> = reader.map(|form| form.ok()).collect();
+
+            // then
+            assert_eq!(vec![Some(Integer(1)), Some(Integer(2))], forms);
+        }
+
+        #[test]
+        fn should_stop_iterating_after_error() {
+            // given
+            let reader = Reader::from_string("1 ) 2 3");
+
+            // when
+            let forms: Vec<_> = reader.map(|form| form.ok()).collect();
+
+            // then
+            assert_eq!(vec![Some(Integer(1)), None], forms);
+        }
+    }
+
+    mod streaming {
+        use super::*;
+        use std::io::{self, BufReader, Cursor, Read};
+
+        /// Input that fails when read, standing for data that has not arrived yet.
+        struct NotYetArrived;
+
+        impl Read for NotYetArrived {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::WouldBlock, "not yet arrived"))
+            }
+        }
+
+        #[test]
+        fn should_read_all_forms_from_reader() -> Result<(), Error> {
+            // given
+            let input = Cursor::new("(a \"ż\") ; comment\n[1 2]".as_bytes().to_vec());
+            let reader = Reader::from_reader(BufReader::with_capacity(1, input));
+
+            // expect
+            assert_eq!(
+                Reader::from_string("(a \"ż\") ; comment\n[1 2]").read_all()?,
+                reader.read_all()?
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn should_read_form_before_rest_of_input_arrives() -> Result<(), Error> {
+            // given
+            let input = Cursor::new(b"(a b)\n".to_vec()).chain(NotYetArrived);
+            let reader = Reader::from_reader(BufReader::new(input));
+
+            // when
+            let first = reader.read()?;
+            let error = reader.read().err().unwrap();
+
+            // then
+            assert_eq!(
+                List(vec![Identifier("a".to_owned()), Identifier("b".to_owned())]),
+                first
+            );
+            match error.downcast::<TokenizerError>() {
+                Ok(TokenizerError::ReadFailed(error)) => {
+                    assert_eq!(io::ErrorKind::WouldBlock, error.kind())
+                }
+                other => panic!("Expected read failure, got {:?}", other),
+            }
+            Ok(())
+        }
+    }
+
+    mod incomplete_input {
+        use super::*;
+
+        fn unclosed(code: &str) -> Option<Unclosed> {
+            let error = Reader::from_string(code).read().err()?;
+            let incomplete = is_incomplete(&error);
+            let unclosed = match error.downcast::<ReaderError>() {
+                Ok(UnexpectedEndOfInput(unclosed, _)) => Some(unclosed),
+                Ok(_) => None,
+                Err(error) => match error.downcast::<TokenizerError>() {
+                    Ok(TokenizerError::Incomplete(unclosed, _)) => Some(unclosed),
+                    _ => None,
+                },
+            };
+            assert_eq!(incomplete, unclosed.is_some());
+            unclosed
+        }
+
+        #[test]
+        fn should_report_what_is_left_open() {
+            // expect
+            assert_eq!(Some(Unclosed::String), unclosed("(print \"hello"));
+            assert_eq!(Some(Unclosed::String), unclosed("\"trailing backslash\\"));
+            assert_eq!(Some(Unclosed::String), unclosed("\"\\u{1F6"));
+            assert_eq!(Some(Unclosed::String), unclosed("#r#\"raw\""));
+            assert_eq!(Some(Unclosed::BlockComment), unclosed("(a #| b"));
+            assert_eq!(Some(Unclosed::Collection('(')), unclosed("(a (b c)"));
+            assert_eq!(Some(Unclosed::Collection('[')), unclosed("[a"));
+            assert_eq!(Some(Unclosed::Collection('{')), unclosed("{a 1"));
+            assert_eq!(Some(Unclosed::Collection('#')), unclosed("#{a"));
+            assert_eq!(Some(Unclosed::ReaderMacro("'")), unclosed("'"));
+            assert_eq!(Some(Unclosed::ReaderMacro("#_")), unclosed("#_ "));
+        }
+
+        #[test]
+        fn should_not_report_malformed_input_as_incomplete() {
+            // expect
+            assert_eq!(None, unclosed("(a]"));
+            assert_eq!(None, unclosed("\"\\q\""));
+            assert_eq!(None, unclosed("1.2.3"));
+            assert_eq!(None, unclosed(""));
+        }
+
+        #[test]
+        fn should_point_at_the_opening_of_incomplete_form() -> Result<(), Error> {
+            // given
+            let error = Reader::from_string("(a\n  [b").read().err().unwrap();
+
+            // expect
+            match error.downcast::<ReaderError>() {
+                Ok(UnexpectedEndOfInput(Unclosed::Collection('['), span)) => {
+                    assert_eq!("2:3", span.to_string())
+                }
+                other => panic!("Expected incomplete input error, got {:?}", other),
+            }
+            Ok(())
+        }
+    }
+
+    mod fuzz {
+        use super::*;
+        use crate::testing::arbitrary_code;
+        use proptest::prelude::*;
+
+        /// Mostly syntax characters, with some that are not ASCII mixed in.
+        const ALPHABET: &[&str] = &[
+            "(", ")", "[", "]", "{", "}", "\"", "\\", "#", "|", "_", ";", "'", "`", "~", "@", ":",
+            "^", " ", "\n", ".", "+", "-", "/", "e", "E", "x", "X", "b", "M", "0", "1", "2", "3",
+            "4", "5", "6", "7", "8", "9", "a", "z", "u", "ż", "\u{0}", "\u{fffd}",
+        ];
+
+        proptest! {
+            #![proptest_config(ProptestConfig::with_cases(20_000))]
+
+            #[test]
+            fn should_never_panic_or_hang_on_arbitrary_input(code in arbitrary_code(ALPHABET)) {
+                // given
+                let reader = Reader::from_string(&code);
+
+                // expect
+                // every successful read consumes input, so this many reads must
+                // end with an error
+                let reads = (0..=code.len() + 1).take_while(|_| reader.read().is_ok());
+                prop_assert!(reads.count() <= code.len(), "reading {:?}", code);
+            }
+        }
+    }
 }
@@ -0,0 +1,74 @@
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+/// Location of a piece of source code.
+///
+/// `line` and `column` are 1-based, `column` counts characters. `start` and `end`
+/// are byte offsets into the source.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Span {
+    pub file: Option<Arc<str>>,
+    pub line: usize,
+    pub column: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// Span starting where `self` starts and ending where `other` ends.
+    pub fn to(&self, other: &Span) -> Span {
+        Span {
+            end: other.end,
+            ..self.clone()
+        }
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file)?;
+        }
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Value together with the place it was read from.
+///
+/// The span does not take part in equality, so spanned values compare structurally.
+#[derive(Debug, Clone)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(node: T, span: Span) -> Self {
+        Self { node, span }
+    }
+}
+
+impl<T: PartialEq> PartialEq for Spanned<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.node == other.node
+    }
+}
+
+/// Spans of a form read by the `Reader`.
+///
+/// For collections `children` holds the spans of the elements in order, so the tree
+/// mirrors the shape of the `Expression` it was read alongside.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SpanTree {
+    pub span: Span,
+    pub children: Vec<SpanTree>,
+}
+
+impl SpanTree {
+    pub fn leaf(span: Span) -> Self {
+        Self {
+            span,
+            children: vec![],
+        }
+    }
+}
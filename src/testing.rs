@@ -0,0 +1,9 @@
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::sample::select;
+
+/// Random source code made of up to 16 pieces of `alphabet`, so that inputs are
+/// mostly syntax and reach deep into the code under test.
+pub(crate) fn arbitrary_code(alphabet: &'static [&'static str]) -> impl Strategy<Value = String> {
+    vec(select(alphabet), 0..16).prop_map(|pieces| pieces.concat())
+}
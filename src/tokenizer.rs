@@ -1,24 +1,85 @@
+use self::source::CharSource;
+use crate::span::{Span, Spanned};
 use crate::tokenizer::TokenizerError::{
-    InvalidNumberCharacter, NotAnEscapableCharacter, UnexpectedEndOfInput,
+    Incomplete, InvalidCharacter, InvalidNumber, InvalidUnicodeEscape, NotAnEscapableCharacter,
+    ReadFailed, UnexpectedEndOfInput,
 };
 use failure::Error;
+use std::cell::RefCell;
+use std::fmt::{Display, Formatter};
+use std::io::BufRead;
+use std::sync::Arc;
+
+mod slice;
+mod source;
+
+pub use self::slice::{SliceToken, SliceTokenizer};
 
 #[derive(Debug, Fail)]
 pub enum TokenizerError {
     #[fail(display = "Unexpected end of input")]
     UnexpectedEndOfInput,
 
-    #[fail(display = "This is not an escapable character: {}", _0)]
-    NotAnEscapableCharacter(char),
+    /// Input ended while something opened at the span was still open, so more
+    /// input could make it valid.
+    #[fail(display = "{}: Incomplete input, unclosed {}", _1, _0)]
+    Incomplete(Unclosed, Span),
+
+    #[fail(display = "{}: This is not an escapable character: {}", _1, _0)]
+    NotAnEscapableCharacter(char, Span),
+
+    #[fail(display = "{}: Invalid unicode escape: {}", _1, _0)]
+    InvalidUnicodeEscape(String, Span),
+
+    #[fail(display = "{}: Invalid number '{}': {}", _2, _0, _1)]
+    InvalidNumber(String, String, Span),
+
+    #[fail(display = "{}: Invalid character literal '\\{}': {}", _2, _0, _1)]
+    InvalidCharacter(String, String, Span),
 
-    #[fail(display = "Invalid character in number: {}", _0)]
-    InvalidNumberCharacter(char),
+    #[fail(display = "Failed to read input: {}", _0)]
+    ReadFailed(#[cause] std::io::Error),
+}
+
+/// What was left open when the input ended.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Unclosed {
+    String,
+    BlockComment,
+    /// List, vector, map or set with the given opening delimiter, `#` for a set.
+    Collection(char),
+    /// Reader macro like `'` or `#_` still waiting for its form.
+    ReaderMacro(&'static str),
+}
+
+impl Display for Unclosed {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            Unclosed::String => f.write_str("string"),
+            Unclosed::BlockComment => f.write_str("block comment"),
+            Unclosed::Collection('[') => f.write_str("vector"),
+            Unclosed::Collection('{') => f.write_str("map"),
+            Unclosed::Collection('#') => f.write_str("set"),
+            Unclosed::Collection(_) => f.write_str("list"),
+            Unclosed::ReaderMacro(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+impl TokenizerError {
+    /// Whether the error means that the input ended too early, as opposed to being
+    /// malformed.
+    pub fn is_incomplete(&self) -> bool {
+        matches!(self, Incomplete(..))
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ValueType {
     String,
     Number,
+    /// Character literal, the value holds the character itself.
+    Char,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -26,59 +87,245 @@ pub enum Token {
     Identifier(String),
     LeftParen,
     RightParen,
+    LeftBracket,
+    RightBracket,
+    LeftBrace,
+    RightBrace,
+    /// `#{` opening a set, closed by a `RightBrace`.
+    LeftSetBrace,
     Value(String, ValueType),
+    /// `#_` - the reader discards the form that follows it.
+    DatumComment,
+    Quote,
+    Quasiquote,
+    Unquote,
+    UnquoteSplicing,
+    /// `^` - attaches metadata to the form that follows it.
+    Meta,
 }
 
-#[derive(Debug, Clone)]
+impl Token {
+    /// Whether this token closes a list, vector or map.
+    pub fn is_closing(&self) -> bool {
+        matches!(
+            self,
+            Token::RightParen | Token::RightBracket | Token::RightBrace
+        )
+    }
+}
+
+#[derive(Debug)]
 pub struct Tokenizer {
-    to_read: Vec<char>,
-    position: usize,
+    source: RefCell<CharSource>,
+    file: Option<Arc<str>>,
+    byte_offset: usize,
+    line: usize,
+    column: usize,
 }
 
 impl Tokenizer {
     pub fn from_string(s: &str) -> Self {
+        Self::from_source(CharSource::from_string(s))
+    }
+
+    /// Tokenizer pulling characters from `input` as tokens are read, so only the
+    /// current token and a few characters after it are held in memory.
+    pub fn from_reader(input: impl BufRead + 'static) -> Self {
+        Self::from_source(CharSource::from_reader(input))
+    }
+
+    fn from_source(source: CharSource) -> Self {
         Self {
-            to_read: s.chars().collect(),
-            position: 0,
+            source: RefCell::new(source),
+            file: None,
+            byte_offset: 0,
+            line: 1,
+            column: 1,
         }
     }
 
+    /// Sets the file name reported in spans of tokens read from now on.
+    pub fn set_file(&mut self, file: &str) {
+        self.file = Some(file.into());
+    }
+
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Result<Token, Error> {
+        self.next_spanned().map(|token| token.node)
+    }
+
+    pub fn next_spanned(&mut self) -> Result<Spanned<Token>, Error> {
+        let token = self.read_spanned();
+        // a failed read looks like the end of input to the rest of the tokenizer
+        match self.source.get_mut().take_error() {
+            Some(error) => Err(ReadFailed(error).into()),
+            None => token,
+        }
+    }
+
+    fn read_spanned(&mut self) -> Result<Spanned<Token>, Error> {
+        self.skip_trivia()?;
+        let start = self.current_span();
+        let token = self.read_token()?;
+        Ok(Spanned::new(token, self.span_from(start)))
+    }
+
+    fn read_token(&mut self) -> Result<Token, Error> {
+        if !self.can_read() {
+            return Err(UnexpectedEndOfInput.into());
+        }
+        match self.peek_char() {
+            '(' => {
+                self.consume_char();
+                Ok(Token::LeftParen)
+            }
+            ')' => {
+                self.consume_char();
+                Ok(Token::RightParen)
+            }
+            '[' => {
+                self.consume_char();
+                Ok(Token::LeftBracket)
+            }
+            ']' => {
+                self.consume_char();
+                Ok(Token::RightBracket)
+            }
+            '{' => {
+                self.consume_char();
+                Ok(Token::LeftBrace)
+            }
+            '}' => {
+                self.consume_char();
+                Ok(Token::RightBrace)
+            }
+            '\'' => {
+                self.consume_char();
+                Ok(Token::Quote)
+            }
+            '`' => {
+                self.consume_char();
+                Ok(Token::Quasiquote)
+            }
+            '~' if self.peek_char_at(1) == Some('@') => {
+                self.consume_char();
+                self.consume_char();
+                Ok(Token::UnquoteSplicing)
+            }
+            '~' => {
+                self.consume_char();
+                Ok(Token::Unquote)
+            }
+            '^' => {
+                self.consume_char();
+                Ok(Token::Meta)
+            }
+            '#' if self.peek_char_at(1) == Some('r') && self.at_raw_string_start() => {
+                self.read_raw_string()
+            }
+            '#' if self.peek_char_at(1) == Some('{') => {
+                self.consume_char();
+                self.consume_char();
+                Ok(Token::LeftSetBrace)
+            }
+            '#' if self.peek_char_at(1) == Some('_') => {
+                self.consume_char();
+                self.consume_char();
+                Ok(Token::DatumComment)
+            }
+            _ if self.at_number_start() => self.read_number(),
+            '"' => self.read_string(),
+            '\\' => self.read_char(),
+            _ => self.read_identifier(),
+        }
+    }
+
+    /// Skips whitespace, commas, line comments and block comments.
+    fn skip_trivia(&mut self) -> Result<(), Error> {
+        while self.can_read() {
+            match self.peek_char() {
+                c if c.is_whitespace() || c == ',' => {
+                    self.consume_char();
+                }
+                ';' => self.skip_line_comment(),
+                '#' if self.peek_char_at(1) == Some('|') => self.skip_block_comment()?,
+                _ => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn skip_line_comment(&mut self) {
+        while self.can_read() && self.consume_char() != '\n' {}
+    }
+
+    /// Skips `#| ... |#` comment, which may contain nested block comments.
+    fn skip_block_comment(&mut self) -> Result<(), Error> {
+        let start = self.current_span();
+        let mut depth = 0;
         loop {
-            if self.can_read() {
-                match self.peek_char() {
-                    '(' => {
-                        self.consume_char();
-                        return Ok(Token::LeftParen);
-                    }
-                    ')' => {
-                        self.consume_char();
-                        return Ok(Token::RightParen);
-                    }
-                    ' ' | '\n' | '\t' => {
-                        self.consume_char();
-                        continue;
-                    }
-                    c if c.is_digit(10) => return self.read_number(),
-                    '"' => return self.read_string(),
-                    _ => return self.read_identifier(),
+            match (self.peek_char_at(0), self.peek_char_at(1)) {
+                (Some('#'), Some('|')) => depth += 1,
+                (Some('|'), Some('#')) => depth -= 1,
+                (Some(_), _) => {
+                    self.consume_char();
+                    continue;
                 }
-            } else {
-                return Err(UnexpectedEndOfInput.into());
+                (None, _) => {
+                    return Err(Incomplete(Unclosed::BlockComment, self.span_from(start)).into())
+                }
+            }
+            self.consume_char();
+            self.consume_char();
+            if depth == 0 {
+                return Ok(());
             }
         }
     }
 
+    /// Empty span at the current position.
+    fn current_span(&self) -> Span {
+        Span {
+            file: self.file.clone(),
+            line: self.line,
+            column: self.column,
+            start: self.byte_offset,
+            end: self.byte_offset,
+        }
+    }
+
+    /// Span from `start` up to the current position.
+    fn span_from(&self, start: Span) -> Span {
+        Span {
+            end: self.byte_offset,
+            ..start
+        }
+    }
+
     fn can_read(&self) -> bool {
-        self.position < self.to_read.len()
+        self.peek_char_at(0).is_some()
     }
     fn peek_char(&self) -> char {
-        self.to_read[self.position]
+        self.peek_char_at(0).expect("peeked past the end of input")
+    }
+
+    fn peek_char_at(&self, offset: usize) -> Option<char> {
+        self.source.borrow_mut().peek(offset)
     }
 
     fn consume_char(&mut self) -> char {
-        let ch = self.peek_char();
-        self.position += 1;
+        let ch = self
+            .source
+            .get_mut()
+            .next()
+            .expect("consumed past the end of input");
+        self.byte_offset += ch.len_utf8();
+        if ch == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
         ch
     }
 
@@ -87,8 +334,7 @@ impl Tokenizer {
         loop {
             if self.can_read() {
                 match self.peek_char() {
-                    '[' | ']' | '{' | '}' | '(' | ')' | ' ' => break,
-                    c if c.is_whitespace() => break,
+                    c if is_delimiter(c) => break,
                     _ => current_token.push(self.consume_char()),
                 }
             } else {
@@ -100,6 +346,7 @@ impl Tokenizer {
     }
 
     fn read_string(&mut self) -> Result<Token, Error> {
+        let start = self.current_span();
         let mut current_token = String::new();
         self.consume_char(); // consume starting quote
         loop {
@@ -110,45 +357,294 @@ impl Tokenizer {
                         break;
                     }
                     '\\' => {
-                        self.consume_char(); // consume '/'
-                        let to_escape = self.consume_char();
-                        let escaped = self.get_escaped_char(to_escape)?;
+                        let escaped = self.read_escape(&start)?;
                         current_token.push(escaped);
                     }
                     _ => current_token.push(self.consume_char()),
                 }
+            } else {
+                return Err(self.incomplete_string(&start));
             }
         }
 
         Ok(Token::Value(current_token, ValueType::String))
     }
 
-    fn get_escaped_char(&self, c: char) -> Result<char, Error> {
-        Ok(match c {
-            'n' => '\n',
-            't' => '\t',
-            '\\' => '\\',
-            _ => return Err(NotAnEscapableCharacter(c).into()),
-        })
+    /// Reads escape sequence starting with a backslash, inside string started at
+    /// `string_start`.
+    fn read_escape(&mut self, string_start: &Span) -> Result<char, Error> {
+        let start = self.current_span();
+        self.consume_char(); // consume '\'
+        if !self.can_read() {
+            return Err(self.incomplete_string(string_start));
+        }
+        let to_escape = self.consume_char();
+        if to_escape == 'u' {
+            return self.read_unicode_escape(start, string_start);
+        }
+        escaped_char(to_escape)
+            .ok_or_else(|| NotAnEscapableCharacter(to_escape, self.span_from(start)).into())
     }
 
-    fn read_number(&mut self) -> Result<Token, Error> {
+    /// Reads the `{1F600}` part of a `\u{1F600}` escape.
+    fn read_unicode_escape(&mut self, start: Span, string_start: &Span) -> Result<char, Error> {
+        let mut digits = String::new();
+        let closed = if self.peek_char_at(0) == Some('{') {
+            self.consume_char();
+            while let Some(c) = self.peek_char_at(0) {
+                if !c.is_ascii_hexdigit() {
+                    break;
+                }
+                digits.push(self.consume_char());
+            }
+            self.peek_char_at(0) == Some('}')
+        } else {
+            false
+        };
+        if !self.can_read() {
+            return Err(self.incomplete_string(string_start));
+        }
+        if !closed {
+            let reason = "expected hex digits in braces, like \\u{1F600}".to_owned();
+            return Err(InvalidUnicodeEscape(reason, self.span_from(start)).into());
+        }
+        self.consume_char(); // consume '}'
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .filter(|_| digits.len() <= 6)
+            .and_then(std::char::from_u32)
+            .ok_or_else(|| {
+                let reason = format!("\\u{{{}}} is not a unicode scalar value", digits);
+                InvalidUnicodeEscape(reason, self.span_from(start)).into()
+            })
+    }
+
+    fn incomplete_string(&self, start: &Span) -> Error {
+        Incomplete(Unclosed::String, self.span_from(start.clone())).into()
+    }
+
+    /// Whether `#r` starts a raw string: it must be followed by `#`s and a quote.
+    fn at_raw_string_start(&self) -> bool {
+        let mut offset = 2;
+        while self.peek_char_at(offset) == Some('#') {
+            offset += 1;
+        }
+        self.peek_char_at(offset) == Some('"')
+    }
+
+    /// Reads raw string like `#r"..."` or `#r##"..."##`. The content is taken
+    /// verbatim and ends at a quote followed by as many `#` as there were after `r`.
+    fn read_raw_string(&mut self) -> Result<Token, Error> {
+        let start = self.current_span();
+        self.consume_char(); // consume '#'
+        self.consume_char(); // consume 'r'
+        let mut hashes = 0;
+        while self.consume_char() == '#' {
+            hashes += 1;
+        }
         let mut current_token = String::new();
         loop {
-            if self.can_read() {
-                match self.peek_char() {
-                    '.' => current_token.push(self.consume_char()),
-                    c if c.is_digit(10) => current_token.push(self.consume_char()),
-                    ' ' | ',' | ')' | ']' | '}' => break,
-                    c if c.is_whitespace() => break,
-                    _ => return Err(InvalidNumberCharacter(self.consume_char()).into()),
+            if !self.can_read() {
+                return Err(self.incomplete_string(&start));
+            }
+            let c = self.consume_char();
+            if c == '"' && (0..hashes).all(|i| self.peek_char_at(i) == Some('#')) {
+                for _ in 0..hashes {
+                    self.consume_char();
                 }
-            } else {
-                break;
+                return Ok(Token::Value(current_token, ValueType::String));
             }
+            current_token.push(c);
         }
+    }
 
-        Ok(Token::Value(current_token, ValueType::Number))
+    /// Whether a number starts here: a digit, optionally preceded by a sign and/or
+    /// a decimal point.
+    fn at_number_start(&self) -> bool {
+        let is_digit = |offset| {
+            self.peek_char_at(offset)
+                .is_some_and(|c: char| c.is_ascii_digit())
+        };
+        let point_or_digit = |offset| match self.peek_char_at(offset) {
+            Some('.') => is_digit(offset + 1),
+            _ => is_digit(offset),
+        };
+        match self.peek_char() {
+            '+' | '-' => point_or_digit(1),
+            _ => point_or_digit(0),
+        }
+    }
+
+    /// Reads character literal like `\\a`, `\\(`, `\\newline` or `\\u00E9`.
+    fn read_char(&mut self) -> Result<Token, Error> {
+        let start = self.current_span();
+        self.consume_char(); // consume '\\'
+        let mut text = String::new();
+        if self.can_read() && !self.peek_char().is_whitespace() {
+            text.push(self.consume_char());
+        }
+        while self.can_read() && !is_delimiter(self.peek_char()) {
+            text.push(self.consume_char());
+        }
+        match char_literal(&text) {
+            Ok(c) => Ok(Token::Value(c.to_string(), ValueType::Char)),
+            Err(reason) => Err(InvalidCharacter(text, reason, self.span_from(start)).into()),
+        }
+    }
+
+    fn read_number(&mut self) -> Result<Token, Error> {
+        let start = self.current_span();
+        let mut current_token = String::new();
+        while self.can_read() && !is_delimiter(self.peek_char()) {
+            current_token.push(self.consume_char());
+        }
+
+        match check_number(&current_token) {
+            Ok(()) => Ok(Token::Value(current_token, ValueType::Number)),
+            Err(reason) => Err(InvalidNumber(current_token, reason, self.span_from(start)).into()),
+        }
+    }
+}
+
+/// Names of characters that are written by name in character literals.
+pub const CHAR_NAMES: &[(&str, char)] = &[
+    ("space", ' '),
+    ("newline", '\n'),
+    ("tab", '\t'),
+    ("return", '\r'),
+    ("backspace", '\u{8}'),
+    ("formfeed", '\u{c}'),
+    ("nul", '\0'),
+];
+
+/// Character written after the backslash of a character literal: the character
+/// itself, its name or `u` followed by four hex digits of its code point.
+fn char_literal(text: &str) -> Result<char, String> {
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (None, _) => return Err("expected a character after '\\'".to_owned()),
+        (Some(c), None) => return Ok(c),
+        _ => {}
+    }
+    if let Some((_, c)) = CHAR_NAMES.iter().find(|(name, _)| *name == text) {
+        return Ok(*c);
+    }
+    match text.strip_prefix('u') {
+        Some(hex) if hex.len() == 4 && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+            u32::from_str_radix(hex, 16)
+                .ok()
+                .and_then(std::char::from_u32)
+                .ok_or_else(|| format!("{} is not a unicode scalar value", hex))
+        }
+        _ => Err("unknown character name".to_owned()),
+    }
+}
+
+/// Character that `\c` stands for in a string, if `c` can be escaped.
+fn escaped_char(c: char) -> Option<char> {
+    Some(match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        '0' => '\0',
+        'b' => '\u{8}',
+        'f' => '\u{c}',
+        '\\' => '\\',
+        '"' => '"',
+        '\'' => '\'',
+        _ => return None,
+    })
+}
+
+fn is_delimiter(c: char) -> bool {
+    match c {
+        '[' | ']' | '{' | '}' | '(' | ')' | ',' | ';' => true,
+        c => c.is_whitespace(),
+    }
+}
+
+/// Validates number literal against the grammar:
+///
+/// ```text
+/// number   = sign? (radix-prefix digits | ratio | decimal)
+/// radix-prefix = '0x' | '0o' | '0b'
+/// ratio    = digits '/' digits
+/// decimal  = (digits ('.' digits?)? | '.' digits) (exponent | 'M')?
+/// exponent = ('e' | 'E') sign? digits
+/// digits   = digit ('_'? digit)*
+/// ```
+fn check_number(text: &str) -> Result<(), String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut position = 0;
+    if let Some('+') | Some('-') = chars.first() {
+        position += 1;
+    }
+
+    let radix = match (chars.get(position), chars.get(position + 1)) {
+        (Some('0'), Some('x')) | (Some('0'), Some('X')) => 16,
+        (Some('0'), Some('o')) | (Some('0'), Some('O')) => 8,
+        (Some('0'), Some('b')) | (Some('0'), Some('B')) => 2,
+        _ => 10,
+    };
+    if radix != 10 {
+        position += 2;
+        if read_digits(&chars, &mut position, radix)? == 0 {
+            return Err(format!("expected digits after '{}'", &text[..position]));
+        }
+    } else {
+        let mut digits = read_digits(&chars, &mut position, 10)?;
+        if digits > 0 && chars.get(position) == Some(&'/') {
+            position += 1;
+            let denominator_start = position;
+            if read_digits(&chars, &mut position, 10)? == 0 {
+                return Err("expected digits in denominator".to_owned());
+            }
+            if chars[denominator_start..position]
+                .iter()
+                .all(|c| *c == '0' || *c == '_')
+            {
+                return Err("denominator must not be zero".to_owned());
+            }
+        } else {
+            if chars.get(position) == Some(&'.') {
+                position += 1;
+                digits += read_digits(&chars, &mut position, 10)?;
+            }
+            if digits == 0 {
+                return Err("expected digits".to_owned());
+            }
+            if chars.get(position) == Some(&'M') {
+                position += 1;
+            } else if let Some('e') | Some('E') = chars.get(position) {
+                position += 1;
+                if let Some('+') | Some('-') = chars.get(position) {
+                    position += 1;
+                }
+                if read_digits(&chars, &mut position, 10)? == 0 {
+                    return Err("expected digits in exponent".to_owned());
+                }
+            }
+        }
+    }
+
+    match chars.get(position) {
+        Some(c) => Err(format!("unexpected character '{}'", c)),
+        None => Ok(()),
+    }
+}
+
+/// Reads digits with optional `_` separators, returning the number of digits read.
+fn read_digits(chars: &[char], position: &mut usize, radix: u32) -> Result<usize, String> {
+    let is_digit = |c: Option<&char>| c.is_some_and(|c| c.is_digit(radix));
+    let mut count = 0;
+    loop {
+        match chars.get(*position) {
+            Some(c) if c.is_digit(radix) => count += 1,
+            Some('_') if count > 0 && is_digit(chars.get(*position + 1)) => {}
+            Some('_') => return Err("digit separator '_' must be placed between digits".to_owned()),
+            _ => return Ok(count),
+        }
+        *position += 1;
     }
 }
 
@@ -297,4 +793,467 @@ mod test {
         );
         assert_eq!(Token::RightParen, tokenizer.next().unwrap());
     }
+
+    mod spans {
+        use super::*;
+
+        #[test]
+        fn should_track_token_spans() {
+            // given
+            let code = "(foo\n  \"bär\" 12)";
+            let mut tokenizer = Tokenizer::from_string(code);
+
+            // when
+            let spans = (0..5)
+                .map(|_| tokenizer.next_spanned().unwrap().span)
+                .map(|span| (span.line, span.column, span.start, span.end))
+                .collect::<Vec<_>>();
+
+            // then
+            assert_eq!(
+                vec![
+                    (1, 1, 0, 1),
+                    (1, 2, 1, 4),
+                    (2, 3, 7, 13),
+                    (2, 9, 14, 16),
+                    (2, 11, 16, 17),
+                ],
+                spans
+            );
+        }
+
+        #[test]
+        fn should_report_file_name_in_spans() {
+            // given
+            let mut tokenizer = Tokenizer::from_string("ident");
+            tokenizer.set_file("script.rp");
+
+            // when
+            let span = tokenizer.next_spanned().unwrap().span;
+
+            // then
+            assert_eq!("script.rp:1:1", span.to_string());
+        }
+
+        #[test]
+        fn should_ignore_spans_when_comparing_tokens() {
+            // given
+            let mut first = Tokenizer::from_string("ident");
+            let mut second = Tokenizer::from_string("\n   ident");
+
+            // expect
+            assert_eq!(
+                first.next_spanned().unwrap(),
+                second.next_spanned().unwrap()
+            );
+        }
+    }
+
+    mod comments {
+        use super::*;
+
+        #[test]
+        fn should_skip_line_comments() {
+            // given
+            let code = "; leading comment\n(foo ; trailing comment\n bar);end";
+            let mut tokenizer = Tokenizer::from_string(code);
+
+            // expect
+            assert_eq!(Token::LeftParen, tokenizer.next().unwrap());
+            assert_eq!(
+                Token::Identifier("foo".to_owned()),
+                tokenizer.next().unwrap()
+            );
+            assert_eq!(
+                Token::Identifier("bar".to_owned()),
+                tokenizer.next().unwrap()
+            );
+            assert_eq!(Token::RightParen, tokenizer.next().unwrap());
+            assert!(tokenizer.next().is_err());
+        }
+
+        #[test]
+        fn should_skip_nested_block_comments() {
+            // given
+            let code = "#| outer #| inner |# still outer |# 12 #||#";
+            let mut tokenizer = Tokenizer::from_string(code);
+
+            // expect
+            assert_eq!(
+                Token::Value("12".to_owned(), ValueType::Number),
+                tokenizer.next().unwrap()
+            );
+            assert!(tokenizer.next().is_err());
+        }
+
+        #[test]
+        fn should_fail_on_unterminated_block_comment() {
+            // given
+            let mut tokenizer = Tokenizer::from_string("#| #| |# ident");
+
+            // expect
+            assert!(tokenizer.next().is_err());
+        }
+
+        #[test]
+        fn should_keep_comment_characters_inside_strings() {
+            // given
+            let code = "\"; not #| a comment\";comment\n\"x\"";
+            let mut tokenizer = Tokenizer::from_string(code);
+
+            // expect
+            assert_eq!(
+                Token::Value("; not #| a comment".to_owned(), ValueType::String),
+                tokenizer.next().unwrap()
+            );
+            assert_eq!(
+                Token::Value("x".to_owned(), ValueType::String),
+                tokenizer.next().unwrap()
+            );
+        }
+
+        #[test]
+        fn should_read_datum_comment() {
+            // given
+            let mut tokenizer = Tokenizer::from_string("#_foo");
+
+            // expect
+            assert_eq!(Token::DatumComment, tokenizer.next().unwrap());
+            assert_eq!(
+                Token::Identifier("foo".to_owned()),
+                tokenizer.next().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn should_read_quoting_tokens() {
+        // given
+        let code = "'a `b ~c ~@d e'";
+        let mut tokenizer = Tokenizer::from_string(code);
+
+        // expect
+        assert_eq!(Token::Quote, tokenizer.next().unwrap());
+        tokenizer.next().unwrap();
+        assert_eq!(Token::Quasiquote, tokenizer.next().unwrap());
+        tokenizer.next().unwrap();
+        assert_eq!(Token::Unquote, tokenizer.next().unwrap());
+        tokenizer.next().unwrap();
+        assert_eq!(Token::UnquoteSplicing, tokenizer.next().unwrap());
+        assert_eq!(Token::Identifier("d".to_owned()), tokenizer.next().unwrap());
+        assert_eq!(
+            Token::Identifier("e'".to_owned()),
+            tokenizer.next().unwrap()
+        );
+    }
+
+    #[test]
+    fn should_read_brackets_and_braces() {
+        // given
+        let code = "[a]{b,c}";
+        let mut tokenizer = Tokenizer::from_string(code);
+
+        // expect
+        assert_eq!(Token::LeftBracket, tokenizer.next().unwrap());
+        assert_eq!(Token::Identifier("a".to_owned()), tokenizer.next().unwrap());
+        assert_eq!(Token::RightBracket, tokenizer.next().unwrap());
+        assert_eq!(Token::LeftBrace, tokenizer.next().unwrap());
+        assert_eq!(Token::Identifier("b".to_owned()), tokenizer.next().unwrap());
+        assert_eq!(Token::Identifier("c".to_owned()), tokenizer.next().unwrap());
+        assert_eq!(Token::RightBrace, tokenizer.next().unwrap());
+    }
+
+    mod numbers {
+        use super::*;
+
+        #[test]
+        fn should_read_number_literals() {
+            for code in &[
+                "0",
+                "-5",
+                "+3",
+                "1e10",
+                "1.5E-3",
+                "0xFF",
+                "-0x1f",
+                "0b1010",
+                "0o17",
+                "1_000_000",
+                ".5",
+                "-.5",
+                "1.",
+                "0xff_00",
+            ] {
+                // given
+                let mut tokenizer = Tokenizer::from_string(code);
+
+                // expect
+                assert_eq!(
+                    Token::Value(code.to_string(), ValueType::Number),
+                    tokenizer.next().unwrap(),
+                    "reading {}",
+                    code
+                );
+            }
+        }
+
+        #[test]
+        fn should_read_signs_and_dots_without_digits_as_identifiers() {
+            for code in &["-", "+", "-foo", "->", ".", ".foo"] {
+                // given
+                let mut tokenizer = Tokenizer::from_string(code);
+
+                // expect
+                assert_eq!(
+                    Token::Identifier(code.to_string()),
+                    tokenizer.next().unwrap()
+                );
+            }
+        }
+
+        #[test]
+        fn should_reject_malformed_numbers() {
+            for (code, reason) in &[
+                ("1.2.3", "unexpected character '.'"),
+                ("3.2xc", "unexpected character 'x'"),
+                ("0x", "expected digits after '0x'"),
+                ("0b102", "unexpected character '2'"),
+                ("1e", "expected digits in exponent"),
+                ("1e+", "expected digits in exponent"),
+                (
+                    "1__000",
+                    "digit separator '_' must be placed between digits",
+                ),
+                ("1_", "digit separator '_' must be placed between digits"),
+                ("0xFF.5", "unexpected character '.'"),
+                ("1/", "expected digits in denominator"),
+                ("1/0", "denominator must not be zero"),
+                ("1/2/3", "unexpected character '/'"),
+                ("1.5/2", "unexpected character '/'"),
+                ("0x1/2", "unexpected character '/'"),
+                ("1e5M", "unexpected character 'M'"),
+                ("1/2M", "unexpected character 'M'"),
+            ] {
+                // given
+                let mut tokenizer = Tokenizer::from_string(code);
+
+                // when
+                let error = tokenizer.next().err().unwrap();
+
+                // then
+                match error.downcast::<TokenizerError>() {
+                    Ok(InvalidNumber(text, actual_reason, span)) => {
+                        assert_eq!(*code, text);
+                        assert_eq!(*reason, actual_reason);
+                        assert_eq!((0, code.len()), (span.start, span.end));
+                    }
+                    other => panic!("Expected invalid number error, got {:?}", other),
+                }
+            }
+        }
+
+        #[test]
+        fn should_end_number_at_delimiter() {
+            // given
+            let mut tokenizer = Tokenizer::from_string("(-12)");
+
+            // expect
+            assert_eq!(Token::LeftParen, tokenizer.next().unwrap());
+            assert_eq!(
+                Token::Value("-12".to_owned(), ValueType::Number),
+                tokenizer.next().unwrap()
+            );
+            assert_eq!(Token::RightParen, tokenizer.next().unwrap());
+        }
+    }
+
+    mod strings {
+        use super::*;
+
+        fn read_string(code: &str) -> String {
+            match Tokenizer::from_string(code).next().unwrap() {
+                Token::Value(value, ValueType::String) => value,
+                other => panic!("Expected string, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn should_read_full_escape_set() {
+            // expect
+            assert_eq!(
+                "\"quoted\" \r\n\t\0\u{8}\u{c}'\\",
+                read_string(r#""\"quoted\" \r\n\t\0\b\f\'\\""#)
+            );
+        }
+
+        #[test]
+        fn should_read_unicode_escapes() {
+            // expect
+            assert_eq!("😀é\u{0}", read_string(r#""\u{1F600}\u{e9}\u{0}""#));
+        }
+
+        #[test]
+        fn should_reject_invalid_unicode_escapes() {
+            for code in &[
+                r#""\u1F600""#,
+                r#""\u{}""#,
+                r#""\u{D800}""#,
+                r#""\u{110000}""#,
+                r#""\u{0000001}""#,
+                r#""\u{12x}""#,
+            ] {
+                // when
+                let error = Tokenizer::from_string(code).next().err().unwrap();
+
+                // then
+                match error.downcast::<TokenizerError>() {
+                    Ok(InvalidUnicodeEscape(_, span)) => assert_eq!(1, span.start),
+                    other => panic!(
+                        "Expected invalid unicode escape for {}, got {:?}",
+                        code, other
+                    ),
+                }
+            }
+        }
+
+        #[test]
+        fn should_point_at_escape_that_is_not_escapable() {
+            // given
+            let code = "\"bad\n \\q escape\"";
+
+            // when
+            let error = Tokenizer::from_string(code).next().err().unwrap();
+
+            // then
+            match error.downcast::<TokenizerError>() {
+                Ok(NotAnEscapableCharacter('q', span)) => {
+                    assert_eq!("2:2", span.to_string());
+                    assert_eq!("\\q", &code[span.start..span.end]);
+                }
+                other => panic!("Expected not escapable character, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn should_read_raw_strings_verbatim() {
+            // expect
+            assert_eq!(r"\d+\.\d*", read_string(r##"#r"\d+\.\d*""##));
+            assert_eq!(
+                "SELECT \"name\"\n  FROM users",
+                read_string("#r#\"SELECT \"name\"\n  FROM users\"#")
+            );
+            assert_eq!("a \"# b", read_string(r###"#r##"a "# b"##"###));
+        }
+
+        #[test]
+        fn should_read_tokens_after_raw_string() {
+            // given
+            let mut tokenizer = Tokenizer::from_string("(#r#\"x\"# #rest)");
+
+            // expect
+            assert_eq!(Token::LeftParen, tokenizer.next().unwrap());
+            assert_eq!(
+                Token::Value("x".to_owned(), ValueType::String),
+                tokenizer.next().unwrap()
+            );
+            assert_eq!(
+                Token::Identifier("#rest".to_owned()),
+                tokenizer.next().unwrap()
+            );
+            assert_eq!(Token::RightParen, tokenizer.next().unwrap());
+        }
+
+        #[test]
+        fn should_fail_on_unterminated_raw_string() {
+            // given
+            let mut tokenizer = Tokenizer::from_string("#r#\"never closed\"");
+
+            // expect
+            assert!(tokenizer.next().is_err());
+        }
+    }
+
+    mod streaming {
+        use super::*;
+        use std::io::{self, BufReader, Cursor, Read};
+
+        /// Input of `count` repetitions of `chunk`, generated as it is read.
+        struct Repeated {
+            chunk: &'static [u8],
+            count: usize,
+            position: usize,
+        }
+
+        impl Read for Repeated {
+            fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+                let mut written = 0;
+                while written < buffer.len() && self.position < self.chunk.len() * self.count {
+                    buffer[written] = self.chunk[self.position % self.chunk.len()];
+                    written += 1;
+                    self.position += 1;
+                }
+                Ok(written)
+            }
+        }
+
+        fn tokens(mut tokenizer: Tokenizer) -> Vec<Spanned<Token>> {
+            std::iter::from_fn(|| tokenizer.next_spanned().ok()).collect()
+        }
+
+        #[test]
+        fn should_read_same_tokens_and_spans_as_from_string() {
+            // given
+            let code = "(fóo\n  \"bär\\u{1F600}\" #r#\"raw\"# 12.5) ; 😀\n#| block |# 'x";
+            let input = BufReader::with_capacity(3, Cursor::new(code.as_bytes().to_vec()));
+
+            // when
+            let streamed = tokens(Tokenizer::from_reader(input));
+
+            // then
+            let expected = tokens(Tokenizer::from_string(code));
+            assert_eq!(expected, streamed);
+            assert_eq!(
+                expected.iter().map(|t| &t.span).collect::<Vec<_>>(),
+                streamed.iter().map(|t| &t.span).collect::<Vec<_>>()
+            );
+        }
+
+        #[test]
+        fn should_decode_invalid_utf8_as_replacement_character() {
+            // given
+            let input = Cursor::new(b"(a\xFF \xE2\x82 b)".to_vec());
+            let mut tokenizer = Tokenizer::from_reader(input);
+
+            // when
+            tokenizer.next().unwrap();
+            let first = tokenizer.next().unwrap();
+            let second = tokenizer.next().unwrap();
+
+            // then
+            assert_eq!(Token::Identifier("a\u{FFFD}".to_owned()), first);
+            assert_eq!(Token::Identifier("\u{FFFD}".to_owned()), second);
+            assert_eq!(Token::Identifier("b".to_owned()), tokenizer.next().unwrap());
+        }
+
+        #[test]
+        fn should_keep_bounded_lookahead_on_large_input() {
+            // given
+            let input = Repeated {
+                chunk: b"(add 12 \"text\") ; note\n",
+                count: 50_000,
+                position: 0,
+            };
+            let mut tokenizer = Tokenizer::from_reader(BufReader::new(input));
+            let mut count = 0;
+
+            // when
+            while tokenizer.next().is_ok() {
+                count += 1;
+                assert!(tokenizer.source.borrow().lookahead_len() <= 3);
+            }
+
+            // then
+            assert_eq!(5 * 50_000, count);
+        }
+    }
 }
@@ -0,0 +1,488 @@
+use super::{char_literal, check_number, escaped_char, is_delimiter, Token, Unclosed, ValueType};
+use crate::span::Span;
+use crate::tokenizer::TokenizerError::{
+    Incomplete, InvalidCharacter, InvalidNumber, InvalidUnicodeEscape, NotAnEscapableCharacter,
+};
+use failure::Error;
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// Token borrowing its text from the tokenized input.
+///
+/// Only strings containing escape sequences own their text.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SliceToken<'a> {
+    Identifier(&'a str),
+    LeftParen,
+    RightParen,
+    LeftBracket,
+    RightBracket,
+    LeftBrace,
+    RightBrace,
+    LeftSetBrace,
+    Value(Cow<'a, str>, ValueType),
+    DatumComment,
+    Quote,
+    Quasiquote,
+    Unquote,
+    UnquoteSplicing,
+    Meta,
+}
+
+impl SliceToken<'_> {
+    pub fn into_owned(self) -> Token {
+        match self {
+            SliceToken::Identifier(name) => Token::Identifier(name.to_owned()),
+            SliceToken::LeftParen => Token::LeftParen,
+            SliceToken::RightParen => Token::RightParen,
+            SliceToken::LeftBracket => Token::LeftBracket,
+            SliceToken::RightBracket => Token::RightBracket,
+            SliceToken::LeftBrace => Token::LeftBrace,
+            SliceToken::RightBrace => Token::RightBrace,
+            SliceToken::LeftSetBrace => Token::LeftSetBrace,
+            SliceToken::Value(value, value_type) => Token::Value(value.into_owned(), value_type),
+            SliceToken::DatumComment => Token::DatumComment,
+            SliceToken::Quote => Token::Quote,
+            SliceToken::Quasiquote => Token::Quasiquote,
+            SliceToken::Unquote => Token::Unquote,
+            SliceToken::UnquoteSplicing => Token::UnquoteSplicing,
+            SliceToken::Meta => Token::Meta,
+        }
+    }
+}
+
+/// Tokenizer working directly on a `&str`.
+///
+/// It reads the same tokens as `Tokenizer`, but tokens borrow their text from the
+/// input and come with the byte range they were read from instead of a full
+/// `Span`. Line and column are only computed for errors.
+#[derive(Debug, Clone)]
+pub struct SliceTokenizer<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> SliceTokenizer<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self { input, position: 0 }
+    }
+
+    /// Next token with its byte range, `None` at the end of input.
+    pub fn next_token(&mut self) -> Result<Option<(SliceToken<'a>, Range<usize>)>, Error> {
+        self.skip_trivia()?;
+        let start = self.position;
+        let c = match self.rest().chars().next() {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        let token = self.read_token(c)?;
+        Ok(Some((token, start..self.position)))
+    }
+
+    fn read_token(&mut self, c: char) -> Result<SliceToken<'a>, Error> {
+        let (token, length) = match c {
+            '(' => (SliceToken::LeftParen, 1),
+            ')' => (SliceToken::RightParen, 1),
+            '[' => (SliceToken::LeftBracket, 1),
+            ']' => (SliceToken::RightBracket, 1),
+            '{' => (SliceToken::LeftBrace, 1),
+            '}' => (SliceToken::RightBrace, 1),
+            '\'' => (SliceToken::Quote, 1),
+            '`' => (SliceToken::Quasiquote, 1),
+            '~' if self.byte_at(1) == Some(b'@') => (SliceToken::UnquoteSplicing, 2),
+            '~' => (SliceToken::Unquote, 1),
+            '^' => (SliceToken::Meta, 1),
+            '#' if self.byte_at(1) == Some(b'r') && self.at_raw_string_start() => {
+                return self.read_raw_string()
+            }
+            '#' if self.byte_at(1) == Some(b'{') => (SliceToken::LeftSetBrace, 2),
+            '#' if self.byte_at(1) == Some(b'_') => (SliceToken::DatumComment, 2),
+            _ if self.at_number_start() => return self.read_number(),
+            '"' => return self.read_string(),
+            '\\' => return self.read_char(),
+            _ => {
+                let text = self.take_until_delimiter();
+                return Ok(SliceToken::Identifier(text));
+            }
+        };
+        self.position += length;
+        Ok(token)
+    }
+
+    fn skip_trivia(&mut self) -> Result<(), Error> {
+        while let Some(c) = self.rest().chars().next() {
+            match c {
+                c if c.is_whitespace() || c == ',' => self.position += c.len_utf8(),
+                ';' => {
+                    self.position = match self.rest().find('\n') {
+                        Some(newline) => self.position + newline + 1,
+                        None => self.input.len(),
+                    }
+                }
+                '#' if self.byte_at(1) == Some(b'|') => self.skip_block_comment()?,
+                _ => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn skip_block_comment(&mut self) -> Result<(), Error> {
+        let start = self.position;
+        let mut depth = 0;
+        loop {
+            match (self.byte_at(0), self.byte_at(1)) {
+                (Some(b'#'), Some(b'|')) => depth += 1,
+                (Some(b'|'), Some(b'#')) => depth -= 1,
+                (Some(_), _) => {
+                    self.position += 1;
+                    continue;
+                }
+                (None, _) => {
+                    self.position = self.input.len();
+                    return Err(Incomplete(Unclosed::BlockComment, self.span_from(start)).into());
+                }
+            }
+            self.position += 2;
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    fn at_raw_string_start(&self) -> bool {
+        let hashes = self.rest()[2..].bytes().take_while(|&b| b == b'#').count();
+        self.byte_at(2 + hashes) == Some(b'"')
+    }
+
+    /// Raw strings never contain escapes, so they are always borrowed.
+    fn read_raw_string(&mut self) -> Result<SliceToken<'a>, Error> {
+        let start = self.position;
+        let hashes = self.rest()[2..].bytes().take_while(|&b| b == b'#').count();
+        let content_start = start + 2 + hashes + 1;
+        let terminator = format!("\"{}", "#".repeat(hashes));
+        match self.input[content_start..].find(&terminator) {
+            Some(length) => {
+                self.position = content_start + length + terminator.len();
+                let content = &self.input[content_start..content_start + length];
+                Ok(SliceToken::Value(Cow::Borrowed(content), ValueType::String))
+            }
+            None => {
+                self.position = self.input.len();
+                Err(Incomplete(Unclosed::String, self.span_from(start)).into())
+            }
+        }
+    }
+
+    fn at_number_start(&self) -> bool {
+        let is_digit = |offset| self.byte_at(offset).is_some_and(|b| b.is_ascii_digit());
+        let point_or_digit = |offset| match self.byte_at(offset) {
+            Some(b'.') => is_digit(offset + 1),
+            _ => is_digit(offset),
+        };
+        match self.byte_at(0) {
+            Some(b'+') | Some(b'-') => point_or_digit(1),
+            _ => point_or_digit(0),
+        }
+    }
+
+    fn read_number(&mut self) -> Result<SliceToken<'a>, Error> {
+        let start = self.position;
+        let text = self.take_until_delimiter();
+        match check_number(text) {
+            Ok(()) => Ok(SliceToken::Value(Cow::Borrowed(text), ValueType::Number)),
+            Err(reason) => {
+                Err(InvalidNumber(text.to_owned(), reason, self.span_from(start)).into())
+            }
+        }
+    }
+
+    /// Reads character literal, borrowing the character when it is written as is.
+    fn read_char(&mut self) -> Result<SliceToken<'a>, Error> {
+        let start = self.position;
+        self.position += 1; // skip '\\'
+        let text_start = self.position;
+        if let Some(c) = self.rest().chars().next().filter(|c| !c.is_whitespace()) {
+            self.position += c.len_utf8();
+        }
+        self.take_until_delimiter();
+        let text = &self.input[text_start..self.position];
+        match char_literal(text) {
+            Ok(c) if c.len_utf8() == text.len() => {
+                Ok(SliceToken::Value(Cow::Borrowed(text), ValueType::Char))
+            }
+            Ok(c) => Ok(SliceToken::Value(
+                Cow::Owned(c.to_string()),
+                ValueType::Char,
+            )),
+            Err(reason) => {
+                Err(InvalidCharacter(text.to_owned(), reason, self.span_from(start)).into())
+            }
+        }
+    }
+
+    /// Reads a string, borrowing it unless it contains escape sequences.
+    fn read_string(&mut self) -> Result<SliceToken<'a>, Error> {
+        let start = self.position;
+        self.position += 1; // skip starting quote
+        let mut unescaped: Option<String> = None;
+        loop {
+            let chunk_start = self.position;
+            let special = self.rest().find(&['"', '\\'][..]);
+            let chunk_end = special.map_or(self.input.len(), |offset| chunk_start + offset);
+            let chunk = &self.input[chunk_start..chunk_end];
+            self.position = chunk_end;
+            match self.byte_at(0) {
+                Some(b'"') => {
+                    self.position += 1;
+                    let value = match unescaped {
+                        Some(mut value) => {
+                            value.push_str(chunk);
+                            Cow::Owned(value)
+                        }
+                        None => Cow::Borrowed(&self.input[start + 1..chunk_end]),
+                    };
+                    return Ok(SliceToken::Value(value, ValueType::String));
+                }
+                Some(_) => {
+                    let escaped = self.read_escape(start)?;
+                    let value = unescaped.get_or_insert_with(String::new);
+                    value.push_str(chunk);
+                    value.push(escaped);
+                }
+                None => return Err(Incomplete(Unclosed::String, self.span_from(start)).into()),
+            }
+        }
+    }
+
+    /// Reads escape sequence starting at the current backslash, inside string
+    /// started at `string_start`.
+    fn read_escape(&mut self, string_start: usize) -> Result<char, Error> {
+        let start = self.position;
+        self.position += 1; // skip '\'
+        let to_escape = match self.rest().chars().next() {
+            Some(c) => c,
+            None => return Err(Incomplete(Unclosed::String, self.span_from(string_start)).into()),
+        };
+        self.position += to_escape.len_utf8();
+        if to_escape != 'u' {
+            return escaped_char(to_escape)
+                .ok_or_else(|| NotAnEscapableCharacter(to_escape, self.span_from(start)).into());
+        }
+        let mut closed = false;
+        let mut digits = "";
+        if self.byte_at(0) == Some(b'{') {
+            self.position += 1;
+            let length = self
+                .rest()
+                .bytes()
+                .take_while(u8::is_ascii_hexdigit)
+                .count();
+            digits = &self.rest()[..length];
+            self.position += length;
+            closed = self.byte_at(0) == Some(b'}');
+        }
+        if self.position == self.input.len() {
+            return Err(Incomplete(Unclosed::String, self.span_from(string_start)).into());
+        }
+        if !closed {
+            let reason = "expected hex digits in braces, like \\u{1F600}".to_owned();
+            return Err(InvalidUnicodeEscape(reason, self.span_from(start)).into());
+        }
+        self.position += 1; // skip '}'
+        u32::from_str_radix(digits, 16)
+            .ok()
+            .filter(|_| digits.len() <= 6)
+            .and_then(std::char::from_u32)
+            .ok_or_else(|| {
+                let reason = format!("\\u{{{}}} is not a unicode scalar value", digits);
+                InvalidUnicodeEscape(reason, self.span_from(start)).into()
+            })
+    }
+
+    fn take_until_delimiter(&mut self) -> &'a str {
+        let start = self.position;
+        let length = self
+            .rest()
+            .find(is_delimiter)
+            .unwrap_or(self.input.len() - start);
+        self.position += length;
+        &self.input[start..self.position]
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    fn byte_at(&self, offset: usize) -> Option<u8> {
+        self.input.as_bytes().get(self.position + offset).cloned()
+    }
+
+    /// Span from byte offset `start` up to the current position.
+    fn span_from(&self, start: usize) -> Span {
+        let before = &self.input[..start];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        Span {
+            file: None,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            start,
+            end: self.position,
+        }
+    }
+}
+
+/// Yields tokens with their byte ranges, stopping after the first error.
+impl<'a> Iterator for SliceTokenizer<'a> {
+    type Item = Result<(SliceToken<'a>, Range<usize>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.next_token();
+        if result.is_err() {
+            self.position = self.input.len();
+        }
+        result.transpose()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::arbitrary_code;
+    use crate::tokenizer::{Tokenizer, TokenizerError};
+    use proptest::prelude::*;
+
+    /// Tokens read by `Tokenizer` with their byte ranges, and the error ending them.
+    fn expected_tokens(code: &str) -> (Vec<(Token, Range<usize>)>, Option<Failure>) {
+        let mut tokenizer = Tokenizer::from_string(code);
+        let mut tokens = vec![];
+        loop {
+            match tokenizer.next_spanned() {
+                Ok(token) => tokens.push((token.node, token.span.start..token.span.end)),
+                Err(error) => return (tokens, Failure::from(error)),
+            }
+        }
+    }
+
+    fn slice_tokens(code: &str) -> (Vec<(Token, Range<usize>)>, Option<Failure>) {
+        let mut tokens = vec![];
+        for result in SliceTokenizer::new(code) {
+            match result {
+                Ok((token, range)) => tokens.push((token.into_owned(), range)),
+                Err(error) => return (tokens, Failure::from(error)),
+            }
+        }
+        (tokens, None)
+    }
+
+    /// Error compared by its message, `None` for a clean end of input.
+    #[derive(Debug, PartialEq)]
+    struct Failure(String);
+
+    impl Failure {
+        fn from(error: Error) -> Option<Failure> {
+            match error.downcast::<TokenizerError>() {
+                Ok(TokenizerError::UnexpectedEndOfInput) => None,
+                Ok(error) => Some(Failure(error.to_string())),
+                Err(error) => Some(Failure(error.to_string())),
+            }
+        }
+    }
+
+    #[test]
+    fn should_read_same_tokens_as_tokenizer() {
+        for code in &[
+            "(say-hello \"John\" 12.5 -3/4 0xFF 1_000 .5M)",
+            "[a {b c}] 'x `(y ~z ~@w) #_ ignored #{1 #{}} ^:k ^{:a 1}x^y",
+            "  ; comment\n (a #| nested #| block |# comment |# b),c",
+            "\"esc\\n\\t\\\\ \\\"q\\\" \\u{1F600}\" \"plain ż\" #r##\"raw \"# \\n\"##",
+            "(fóo\n  \"bär\" 12) ; 😀\n#rest #r #| x |#",
+            "+ - +a -.5 .x ... +.e1",
+            "",
+            "(a \"unterminated",
+            "\"bad \\q escape\"",
+            "\"\\u{110000}\"",
+            "\"\\u{12\"",
+            "\"\\u",
+            "1.2.3",
+            "#| never closed",
+            "#r#\"never closed\"",
+            "(\\a \\( \\) \\é \\space \\u00E9 \\newline)",
+            "\\",
+            "\\ a",
+            "\\unknown",
+        ] {
+            // expect
+            assert_eq!(
+                expected_tokens(code),
+                slice_tokens(code),
+                "tokenizing {:?}",
+                code
+            );
+        }
+    }
+
+    const ALPHABET: &[&str] = &[
+        "(", ")", "[", "]", "{", "}", "\"", "\\", "#", "|", "_", ";", "'", "`", "~", "@", "^", "r",
+        " ", ",", "\n", ".", "+", "-", "/", "e", "E", "x", "0", "1", "2", "3", "4", "5", "6", "7",
+        "8", "9", "M", "a", "u", "ż",
+    ];
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(20_000))]
+
+        #[test]
+        fn should_read_same_tokens_as_tokenizer_for_arbitrary_input(code in arbitrary_code(ALPHABET)) {
+            // expect
+            prop_assert_eq!(
+                expected_tokens(&code),
+                slice_tokens(&code),
+                "tokenizing {:?}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn should_borrow_text_without_escapes() {
+        // given
+        let code = "(ident \"plain\" #r\"raw\\n\" 12 \"esc\\n\")";
+
+        // when
+        let tokens: Vec<_> = SliceTokenizer::new(code)
+            .map(|result| result.unwrap().0)
+            .collect();
+
+        // then
+        assert_eq!(SliceToken::Identifier("ident"), tokens[1]);
+        for token in &tokens[2..5] {
+            match token {
+                SliceToken::Value(Cow::Borrowed(_), _) => {}
+                other => panic!("Expected borrowed value, got {:?}", other),
+            }
+        }
+        assert_eq!(
+            SliceToken::Value(Cow::Owned("esc\n".to_owned()), ValueType::String),
+            tokens[5]
+        );
+    }
+
+    #[test]
+    fn should_report_error_position() {
+        // given
+        let mut tokenizer = SliceTokenizer::new("(a\n  1x)");
+
+        // when
+        let error = tokenizer.find_map(Result::err).unwrap();
+
+        // then
+        match error.downcast::<TokenizerError>() {
+            Ok(TokenizerError::InvalidNumber(_, _, span)) => {
+                assert_eq!(
+                    ("2:3".to_owned(), 5, 7),
+                    (span.to_string(), span.start, span.end)
+                )
+            }
+            other => panic!("Expected invalid number error, got {:?}", other),
+        }
+    }
+}
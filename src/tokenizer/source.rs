@@ -0,0 +1,116 @@
+use std::collections::VecDeque;
+use std::fmt::{Debug, Formatter};
+use std::io::{self, BufRead};
+
+/// Characters of the tokenizer input.
+///
+/// Characters coming from a reader are decoded only when the tokenizer looks at
+/// them, and only those it has peeked at but not consumed yet are kept in memory.
+/// Invalid UTF-8 sequences are decoded as U+FFFD.
+pub struct CharSource {
+    lookahead: VecDeque<char>,
+    input: Option<Box<dyn BufRead>>,
+    error: Option<io::Error>,
+}
+
+impl CharSource {
+    pub fn from_string(s: &str) -> Self {
+        Self {
+            lookahead: s.chars().collect(),
+            input: None,
+            error: None,
+        }
+    }
+
+    pub fn from_reader(input: impl BufRead + 'static) -> Self {
+        Self {
+            lookahead: VecDeque::new(),
+            input: Some(Box::new(input)),
+            error: None,
+        }
+    }
+
+    /// Character `offset` places ahead of the next one, `None` past the end of input.
+    pub fn peek(&mut self, offset: usize) -> Option<char> {
+        while self.lookahead.len() <= offset {
+            let c = self.decode_char()?;
+            self.lookahead.push_back(c);
+        }
+        self.lookahead.get(offset).cloned()
+    }
+
+    pub fn next(&mut self) -> Option<char> {
+        self.peek(0)?;
+        self.lookahead.pop_front()
+    }
+
+    /// Error that ended reading of the input, if any. The input counts as ended
+    /// after an error.
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+
+    #[cfg(test)]
+    pub fn lookahead_len(&self) -> usize {
+        self.lookahead.len()
+    }
+
+    fn decode_char(&mut self) -> Option<char> {
+        let input = self.input.as_mut()?;
+        let mut bytes = [0; 4];
+        let mut len = 0;
+        let mut width = 1;
+        while len < width {
+            let byte = match input.fill_buf() {
+                Ok(buffer) => match buffer.first() {
+                    Some(&byte) => byte,
+                    None => break,
+                },
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => {
+                    self.error = Some(error);
+                    self.input = None;
+                    return None;
+                }
+            };
+            if len == 0 {
+                width = utf8_width(byte);
+            } else if byte & 0xC0 != 0x80 {
+                // not a continuation byte, it starts the next character
+                break;
+            }
+            input.consume(1);
+            bytes[len] = byte;
+            len += 1;
+        }
+        if len == 0 {
+            self.input = None;
+            return None;
+        }
+        let decoded = std::str::from_utf8(&bytes[..len]).ok();
+        Some(
+            decoded
+                .and_then(|s| s.chars().next())
+                .unwrap_or(char::REPLACEMENT_CHARACTER),
+        )
+    }
+}
+
+/// Length of the UTF-8 sequence starting with `byte`, 1 for invalid leading bytes.
+fn utf8_width(byte: u8) -> usize {
+    match byte {
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF7 => 4,
+        _ => 1,
+    }
+}
+
+impl Debug for CharSource {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        f.debug_struct("CharSource")
+            .field("lookahead", &self.lookahead)
+            .field("streaming", &self.input.is_some())
+            .finish()
+    }
+}